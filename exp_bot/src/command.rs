@@ -1,14 +1,100 @@
 use std::str::FromStr;
 
 use anyhow::Context;
-use chrono::{Days, NaiveDate, Utc};
+use chrono::{Datelike, Days, NaiveDate, Utc};
 use teloxide_core::types::MessageId;
 
+use crate::{conversation_state::ExpenseField, report::Chart};
+
 pub enum Command {
     AddCategory,
-    ConfirmCategoryName { msg_id: MessageId },
-    RejectCategoryName { msg_id: MessageId },
-    PickExpenseDate { msg_id: MessageId, date: NaiveDate },
+    ConfirmCategoryName {
+        msg_id: MessageId,
+    },
+    RejectCategoryName {
+        msg_id: MessageId,
+    },
+    PickExpenseDate {
+        msg_id: MessageId,
+        date: NaiveDate,
+    },
+    ExpenseCalendar {
+        msg_id: MessageId,
+        year: i32,
+        month: u32,
+    },
+    Noop,
+    PickCategory {
+        category_id: i64,
+    },
+    CategoriesPage {
+        page: usize,
+    },
+    ReportYear {
+        year: i32,
+    },
+    Report {
+        year: i32,
+        month: u32,
+        chart: Chart,
+    },
+    ConfirmQuickAdd {
+        msg_id: MessageId,
+    },
+    RejectQuickAdd {
+        msg_id: MessageId,
+    },
+    ConfirmImport {
+        msg_id: MessageId,
+    },
+    RejectImport {
+        msg_id: MessageId,
+    },
+    HistoryPage {
+        page: usize,
+    },
+    HistoryExpense {
+        expense_id: i64,
+        page: usize,
+    },
+    EditExpense {
+        expense_id: i64,
+        field: ExpenseField,
+    },
+    DeleteExpense {
+        expense_id: i64,
+        page: usize,
+    },
+    ManageCategories {
+        page: usize,
+    },
+    ManageCategory {
+        category_id: i64,
+        page: usize,
+    },
+    RenameCategory {
+        category_id: i64,
+    },
+    MergeCategory {
+        category_id: i64,
+    },
+    ConfirmMerge {
+        category_id: i64,
+        into_id: i64,
+    },
+    RejectMerge,
+    ArchiveCategory {
+        category_id: i64,
+        page: usize,
+    },
+    DeleteCategory {
+        category_id: i64,
+        page: usize,
+    },
+    ConfirmDeleteCategory {
+        category_id: i64,
+        page: usize,
+    },
 }
 
 impl FromStr for Command {
@@ -64,9 +150,239 @@ impl FromStr for Command {
                 });
             }
 
+            // a day picked in the calendar (`20230714`)
+            if let Ok(date) = NaiveDate::parse_from_str(kind, "%Y%m%d") {
+                return Ok(Command::PickExpenseDate {
+                    msg_id: MessageId(msg_id),
+                    date,
+                });
+            }
+
             anyhow::bail!("failed to parse command (code: ped, data: {data})");
         }
 
+        if let Some(data) = cmd.strip_prefix("cal:") {
+            let [msg_id, month] = data.split(':').collect::<Vec<_>>()[..] else {
+                anyhow::bail!("failed to parse command (code: cal, data: {data})");
+            };
+
+            let msg_id = msg_id.parse::<i32>().context(format!(
+                "failed to parse message id (code: cal, data: {data})"
+            ))?;
+            // `202307`
+            let date = NaiveDate::parse_from_str(&format!("{month}01"), "%Y%m%d")
+                .context(format!("failed to parse month (code: cal, data: {data})"))?;
+
+            return Ok(Command::ExpenseCalendar {
+                msg_id: MessageId(msg_id),
+                year: date.year(),
+                month: date.month(),
+            });
+        }
+
+        // buttons which are there just to be looked at
+        if cmd == "noop" {
+            return Ok(Command::Noop);
+        }
+
+        if let Some(data) = cmd.strip_prefix("pkc:") {
+            let category_id = data.parse::<i64>().context(format!(
+                "failed to parse category id (code: pkc, data: {data})"
+            ))?;
+
+            return Ok(Command::PickCategory { category_id });
+        }
+
+        if let Some(data) = cmd.strip_prefix("ctp:") {
+            let page = data
+                .parse::<usize>()
+                .context(format!("failed to parse page (code: ctp, data: {data})"))?;
+
+            return Ok(Command::CategoriesPage { page });
+        }
+
+        if let Some(data) = cmd.strip_prefix("rpy:") {
+            let year = data
+                .parse::<i32>()
+                .context(format!("failed to parse year (code: rpy, data: {data})"))?;
+
+            return Ok(Command::ReportYear { year });
+        }
+
+        if let Some(data) = cmd.strip_prefix("rpm:") {
+            let [year, month, chart] = data.split(':').collect::<Vec<_>>()[..] else {
+                anyhow::bail!("failed to parse command (code: rpm, data: {data})");
+            };
+
+            let year = year
+                .parse::<i32>()
+                .context(format!("failed to parse year (code: rpm, data: {data})"))?;
+            let month = month
+                .parse::<u32>()
+                .ok()
+                .filter(|month| (1..=12).contains(month))
+                .context(format!("failed to parse month (code: rpm, data: {data})"))?;
+            let chart = Chart::from_code(chart)
+                .context(format!("failed to parse chart (code: rpm, data: {data})"))?;
+
+            return Ok(Command::Report { year, month, chart });
+        }
+
+        if let Some(data) = cmd.strip_prefix("cqa:") {
+            let msg_id = data.parse::<i32>().context(format!(
+                "failed to parse message id (code: cqa, data: {data})"
+            ))?;
+
+            return Ok(Command::ConfirmQuickAdd {
+                msg_id: MessageId(msg_id),
+            });
+        }
+
+        if let Some(data) = cmd.strip_prefix("rqa:") {
+            let msg_id = data.parse::<i32>().context(format!(
+                "failed to parse message id (code: rqa, data: {data})"
+            ))?;
+
+            return Ok(Command::RejectQuickAdd {
+                msg_id: MessageId(msg_id),
+            });
+        }
+
+        if let Some(data) = cmd.strip_prefix("cim:") {
+            let msg_id = data.parse::<i32>().context(format!(
+                "failed to parse message id (code: cim, data: {data})"
+            ))?;
+
+            return Ok(Command::ConfirmImport {
+                msg_id: MessageId(msg_id),
+            });
+        }
+
+        if let Some(data) = cmd.strip_prefix("rim:") {
+            let msg_id = data.parse::<i32>().context(format!(
+                "failed to parse message id (code: rim, data: {data})"
+            ))?;
+
+            return Ok(Command::RejectImport {
+                msg_id: MessageId(msg_id),
+            });
+        }
+
+        if let Some(data) = cmd.strip_prefix("hsp:") {
+            let page = data
+                .parse::<usize>()
+                .context(format!("failed to parse page (code: hsp, data: {data})"))?;
+
+            return Ok(Command::HistoryPage { page });
+        }
+
+        // the page is the one to go back to
+        if let Some(data) = cmd.strip_prefix("hsx:") {
+            let (expense_id, page) = id_and_page("hsx", data)?;
+            return Ok(Command::HistoryExpense { expense_id, page });
+        }
+
+        if let Some(data) = cmd.strip_prefix("hse:") {
+            let [expense_id, field] = data.split(':').collect::<Vec<_>>()[..] else {
+                anyhow::bail!("failed to parse command (code: hse, data: {data})");
+            };
+
+            let expense_id = expense_id.parse::<i64>().context(format!(
+                "failed to parse expense id (code: hse, data: {data})"
+            ))?;
+            let field = ExpenseField::from_code(field)
+                .context(format!("failed to parse field (code: hse, data: {data})"))?;
+
+            return Ok(Command::EditExpense { expense_id, field });
+        }
+
+        if let Some(data) = cmd.strip_prefix("hsd:") {
+            let (expense_id, page) = id_and_page("hsd", data)?;
+            return Ok(Command::DeleteExpense { expense_id, page });
+        }
+
+        if let Some(data) = cmd.strip_prefix("mcp:") {
+            let page = data
+                .parse::<usize>()
+                .context(format!("failed to parse page (code: mcp, data: {data})"))?;
+
+            return Ok(Command::ManageCategories { page });
+        }
+
+        if let Some(data) = cmd.strip_prefix("mcx:") {
+            let (category_id, page) = id_and_page("mcx", data)?;
+            return Ok(Command::ManageCategory { category_id, page });
+        }
+
+        if let Some(data) = cmd.strip_prefix("mcr:") {
+            let category_id = data.parse::<i64>().context(format!(
+                "failed to parse category id (code: mcr, data: {data})"
+            ))?;
+
+            return Ok(Command::RenameCategory { category_id });
+        }
+
+        if let Some(data) = cmd.strip_prefix("mcm:") {
+            let category_id = data.parse::<i64>().context(format!(
+                "failed to parse category id (code: mcm, data: {data})"
+            ))?;
+
+            return Ok(Command::MergeCategory { category_id });
+        }
+
+        if let Some(data) = cmd.strip_prefix("mmy:") {
+            let [category_id, into_id] = data.split(':').collect::<Vec<_>>()[..] else {
+                anyhow::bail!("failed to parse command (code: mmy, data: {data})");
+            };
+
+            let category_id = category_id.parse::<i64>().context(format!(
+                "failed to parse category id (code: mmy, data: {data})"
+            ))?;
+            let into_id = into_id.parse::<i64>().context(format!(
+                "failed to parse category id (code: mmy, data: {data})"
+            ))?;
+
+            return Ok(Command::ConfirmMerge {
+                category_id,
+                into_id,
+            });
+        }
+
+        if cmd == "mmn" {
+            return Ok(Command::RejectMerge);
+        }
+
+        if let Some(data) = cmd.strip_prefix("mca:") {
+            let (category_id, page) = id_and_page("mca", data)?;
+            return Ok(Command::ArchiveCategory { category_id, page });
+        }
+
+        if let Some(data) = cmd.strip_prefix("mcd:") {
+            let (category_id, page) = id_and_page("mcd", data)?;
+            return Ok(Command::DeleteCategory { category_id, page });
+        }
+
+        if let Some(data) = cmd.strip_prefix("mdy:") {
+            let (category_id, page) = id_and_page("mdy", data)?;
+            return Ok(Command::ConfirmDeleteCategory { category_id, page });
+        }
+
         anyhow::bail!("unknown cmd: {cmd}");
     }
 }
+
+// `<id>:<page>` where the id is of an expense or of a category
+fn id_and_page(code: &str, data: &str) -> anyhow::Result<(i64, usize)> {
+    let [id, page] = data.split(':').collect::<Vec<_>>()[..] else {
+        anyhow::bail!("failed to parse command (code: {code}, data: {data})");
+    };
+
+    let id = id
+        .parse::<i64>()
+        .context(format!("failed to parse id (code: {code}, data: {data})"))?;
+    let page = page
+        .parse::<usize>()
+        .context(format!("failed to parse page (code: {code}, data: {data})"))?;
+
+    Ok((id, page))
+}
@@ -1,8 +1,19 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
-use chrono::NaiveDate;
-use teloxide_core::types::{MessageId, UserId};
+use anyhow::Context;
+use chrono::{DateTime, NaiveDate, Utc};
+use deadpool_postgres::Object;
+use rust_decimal::Decimal;
+use serde_json::{json, Value};
+use teloxide_core::types::{ChatId, MessageId, UserId};
 use tokio::sync::RwLock;
+use tokio_postgres::Row;
+
+use crate::{retry::retry, PgPool};
+
+// bumped whenever the json layout of the states changes. states
+// of other versions are dropped (the user just starts over)
+const VERSION: i16 = 1;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum ConversationState {
@@ -19,29 +30,439 @@ pub enum ConversationState {
         category_name: String,
         date: NaiveDate,
     },
+    // a quick-add line with a category which does not exist yet
+    AwaitingQuickAddConfirmation {
+        msg_id: MessageId,
+        category_name: String,
+        amounts: Vec<Decimal>,
+        date: NaiveDate,
+    },
+    // all (date, category, amount) rows of a month file waiting to be
+    // imported, the ones recorded by then are skipped on confirmation
+    AwaitingImportConfirmation {
+        msg_id: MessageId,
+        expenses: Vec<(NaiveDate, String, Decimal)>,
+    },
+    EditingExpense {
+        expense_id: i64,
+        field: ExpenseField,
+    },
+    RenamingCategory {
+        category_id: i64,
+    },
+    // waiting for the category to merge into
+    MergingCategory {
+        category_id: i64,
+    },
+}
+
+// what of a recorded expense is being changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ExpenseField {
+    Amount,
+    Category,
+    Date,
+}
+
+impl ExpenseField {
+    // the same codes in callback data and in stored states
+    pub fn code(self) -> &'static str {
+        match self {
+            ExpenseField::Amount => "a",
+            ExpenseField::Category => "c",
+            ExpenseField::Date => "d",
+        }
+    }
+
+    pub fn from_code(code: &str) -> anyhow::Result<Self> {
+        match code {
+            "a" => Ok(ExpenseField::Amount),
+            "c" => Ok(ExpenseField::Category),
+            "d" => Ok(ExpenseField::Date),
+            _ => anyhow::bail!("unknown expense field code: {code}"),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ExpenseField::Amount => "amount",
+            ExpenseField::Category => "category",
+            ExpenseField::Date => "date",
+        }
+    }
+}
+
+impl ConversationState {
+    fn to_json(&self) -> Value {
+        match self {
+            ConversationState::AwaitingCategoryName => json!({
+                "kind": "awaiting_category_name",
+            }),
+            ConversationState::AwaitingCategoryNameConfirmation {
+                msg_id,
+                category_name,
+            } => json!({
+                "kind": "awaiting_category_name_confirmation",
+                "msg_id": msg_id.0,
+                "category_name": category_name,
+            }),
+            ConversationState::AwaitingExpenseDate {
+                msg_id,
+                category_name,
+            } => json!({
+                "kind": "awaiting_expense_date",
+                "msg_id": msg_id.0,
+                "category_name": category_name,
+            }),
+            ConversationState::AwaitingExpenseAmount {
+                category_name,
+                date,
+            } => json!({
+                "kind": "awaiting_expense_amount",
+                "category_name": category_name,
+                "date": date.to_string(),
+            }),
+            ConversationState::AwaitingQuickAddConfirmation {
+                msg_id,
+                category_name,
+                amounts,
+                date,
+            } => json!({
+                "kind": "awaiting_quick_add_confirmation",
+                "msg_id": msg_id.0,
+                "category_name": category_name,
+                // strings, so that no precision is lost
+                "amounts": amounts.iter().map(Decimal::to_string).collect::<Vec<_>>(),
+                "date": date.to_string(),
+            }),
+            ConversationState::AwaitingImportConfirmation { msg_id, expenses } => json!({
+                "kind": "awaiting_import_confirmation",
+                "msg_id": msg_id.0,
+                "expenses": expenses
+                    .iter()
+                    .map(|(date, category, amount)| {
+                        json!([date.to_string(), category, amount.to_string()])
+                    })
+                    .collect::<Vec<_>>(),
+            }),
+            ConversationState::EditingExpense { expense_id, field } => json!({
+                "kind": "editing_expense",
+                "expense_id": expense_id,
+                "field": field.code(),
+            }),
+            ConversationState::RenamingCategory { category_id } => json!({
+                "kind": "renaming_category",
+                "category_id": category_id,
+            }),
+            ConversationState::MergingCategory { category_id } => json!({
+                "kind": "merging_category",
+                "category_id": category_id,
+            }),
+        }
+    }
+
+    fn from_json(state: &Value) -> anyhow::Result<Self> {
+        let field = |name: &str| state.get(name).context(format!("missing field: {name}"));
+        let string = |name: &str| {
+            field(name)?
+                .as_str()
+                .map(str::to_string)
+                .context(format!("not a string: {name}"))
+        };
+        let msg_id = || {
+            field("msg_id")?
+                .as_i64()
+                .and_then(|id| i32::try_from(id).ok())
+                .map(MessageId)
+                .context("not a message id: msg_id")
+        };
+
+        let category_id = || {
+            field("category_id")?
+                .as_i64()
+                .context("not a category id: category_id")
+        };
+
+        let kind = string("kind")?;
+        let state = match kind.as_str() {
+            "awaiting_category_name" => ConversationState::AwaitingCategoryName,
+            "awaiting_category_name_confirmation" => {
+                ConversationState::AwaitingCategoryNameConfirmation {
+                    msg_id: msg_id()?,
+                    category_name: string("category_name")?,
+                }
+            }
+            "awaiting_expense_date" => ConversationState::AwaitingExpenseDate {
+                msg_id: msg_id()?,
+                category_name: string("category_name")?,
+            },
+            "awaiting_expense_amount" => ConversationState::AwaitingExpenseAmount {
+                category_name: string("category_name")?,
+                date: string("date")?
+                    .parse::<NaiveDate>()
+                    .context("failed to parse date")?,
+            },
+            "awaiting_quick_add_confirmation" => ConversationState::AwaitingQuickAddConfirmation {
+                msg_id: msg_id()?,
+                category_name: string("category_name")?,
+                amounts: field("amounts")?
+                    .as_array()
+                    .context("not an array: amounts")?
+                    .iter()
+                    .map(|amount| {
+                        amount
+                            .as_str()
+                            .and_then(|amount| Decimal::from_str_exact(amount).ok())
+                            .context("not an amount: amounts")
+                    })
+                    .collect::<anyhow::Result<_>>()?,
+                date: string("date")?
+                    .parse::<NaiveDate>()
+                    .context("failed to parse date")?,
+            },
+            "awaiting_import_confirmation" => ConversationState::AwaitingImportConfirmation {
+                msg_id: msg_id()?,
+                expenses: field("expenses")?
+                    .as_array()
+                    .context("not an array: expenses")?
+                    .iter()
+                    .map(|row| {
+                        let Some([date, category, amount]) = row.as_array().map(Vec::as_slice)
+                        else {
+                            anyhow::bail!("not a row: expenses");
+                        };
+
+                        let (Some(date), Some(category), Some(amount)) =
+                            (date.as_str(), category.as_str(), amount.as_str())
+                        else {
+                            anyhow::bail!("not a row: expenses");
+                        };
+
+                        Ok((
+                            date.parse::<NaiveDate>().context("failed to parse date")?,
+                            category.to_string(),
+                            Decimal::from_str_exact(amount).context("failed to parse amount")?,
+                        ))
+                    })
+                    .collect::<anyhow::Result<_>>()?,
+            },
+            "editing_expense" => ConversationState::EditingExpense {
+                expense_id: field("expense_id")?
+                    .as_i64()
+                    .context("not an expense id: expense_id")?,
+                field: ExpenseField::from_code(&string("field")?)?,
+            },
+            "renaming_category" => ConversationState::RenamingCategory {
+                category_id: category_id()?,
+            },
+            "merging_category" => ConversationState::MergingCategory {
+                category_id: category_id()?,
+            },
+            _ => anyhow::bail!("unknown kind: {kind}"),
+        };
+
+        Ok(state)
+    }
 }
 
-#[derive(Default)]
+// a state along with where the conversation happens
+#[derive(Debug, Clone)]
+pub struct Conversation {
+    pub state: ConversationState,
+    pub chat_id: ChatId,
+    // the inline keyboard the conversation waits on (if any)
+    pub keyboard: Option<MessageId>,
+    updated_at: DateTime<Utc>,
+}
+
+// states live in the db so that they survive restarts. the cache in front of it
+// also remembers users without a state so that most messages never hit the db.
+// states which have not changed for `ttl` are treated as gone
 pub struct ConversationStates {
-    inner: Arc<RwLock<HashMap<UserId, ConversationState>>>,
+    pool: PgPool,
+    ttl: Duration,
+    cache: Arc<RwLock<HashMap<UserId, Option<Conversation>>>>,
 }
 
 impl ConversationStates {
-    pub async fn set(&self, user_id: UserId, state: ConversationState) {
+    pub fn new(pool: PgPool, ttl: Duration) -> Self {
+        Self {
+            pool,
+            ttl,
+            cache: Default::default(),
+        }
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    // see `Storage::client`
+    async fn client(&self) -> anyhow::Result<Object> {
+        self.pool.get().await.context("failed to get db connection")
+    }
+
+    pub async fn set(
+        &self,
+        user_id: UserId,
+        chat_id: ChatId,
+        state: ConversationState,
+        keyboard: Option<MessageId>,
+    ) -> anyhow::Result<()> {
+        let client = self.client().await?;
         tracing::debug!(
             user_id = user_id.0,
             state = debug(&state),
             "set conversation state"
         );
 
-        self.inner.write().await.insert(user_id, state);
+        let id = i64::try_from(user_id.0).context("failed to cast user id to i64")?;
+        let json = state.to_json();
+        let keyboard_msg_id = keyboard.map(|msg_id| msg_id.0);
+        let row = retry(|| async {
+            client
+                .query_one(
+                    "insert into conversation_states(user_id, version, state, chat_id, keyboard_msg_id)
+                     values($1, $2, $3, $4, $5)
+                     on conflict (user_id) do update
+                     set version = excluded.version, state = excluded.state, chat_id = excluded.chat_id,
+                         keyboard_msg_id = excluded.keyboard_msg_id, updated_at = now()
+                     returning updated_at",
+                    &[&id, &VERSION, &json, &chat_id.0, &keyboard_msg_id],
+                )
+                .await
+        })
+        .await
+        .context("failed to save conversation state")?;
+
+        let conversation = Conversation {
+            state,
+            chat_id,
+            keyboard,
+            updated_at: row.get(0),
+        };
+        self.cache.write().await.insert(user_id, Some(conversation));
+
+        Ok(())
     }
 
-    pub async fn get(&self, user_id: UserId) -> Option<ConversationState> {
-        self.inner.read().await.get(&user_id).cloned()
+    pub async fn get(&self, user_id: UserId) -> anyhow::Result<Option<ConversationState>> {
+        Ok(self
+            .conversation(user_id)
+            .await?
+            .map(|conversation| conversation.state))
     }
 
-    pub async fn clear(&self, user_id: UserId) {
-        self.inner.write().await.remove(&user_id);
+    pub async fn conversation(&self, user_id: UserId) -> anyhow::Result<Option<Conversation>> {
+        let cached = self.cache.read().await.get(&user_id).cloned();
+        let conversation = match cached {
+            Some(conversation) => conversation,
+            None => {
+                let conversation = self.load(user_id).await?;
+                self.cache
+                    .write()
+                    .await
+                    .insert(user_id, conversation.clone());
+                conversation
+            }
+        };
+
+        // expired ones are cleaned up (and the user is told) by `expire`
+        Ok(conversation.filter(|conversation| !self.expired(conversation)))
+    }
+
+    // the cleared conversation (if any), expired or not
+    pub async fn clear(&self, user_id: UserId) -> anyhow::Result<Option<Conversation>> {
+        let client = self.client().await?;
+        let conversation = match self.cache.read().await.get(&user_id).cloned() {
+            Some(conversation) => conversation,
+            None => self.load(user_id).await?,
+        };
+
+        let id = i64::try_from(user_id.0).context("failed to cast user id to i64")?;
+        retry(|| async {
+            client
+                .execute("delete from conversation_states where user_id = $1", &[&id])
+                .await
+        })
+        .await
+        .context("failed to clear conversation state")?;
+
+        self.cache.write().await.insert(user_id, None);
+
+        Ok(conversation)
+    }
+
+    // removes the conversations which have been idle for longer than the ttl
+    pub async fn expire(&self) -> anyhow::Result<Vec<Conversation>> {
+        let client = self.client().await?;
+        let ttl = chrono::Duration::from_std(self.ttl).context("ttl is too long")?;
+        let deadline = Utc::now() - ttl;
+        let rows = retry(|| async {
+            client
+                .query(
+                    "delete from conversation_states where updated_at < $1
+                     returning user_id, version, state, chat_id, keyboard_msg_id, updated_at",
+                    &[&deadline],
+                )
+                .await
+        })
+        .await
+        .context("failed to expire conversation states")?;
+
+        let mut expired = vec![];
+        let mut cache = self.cache.write().await;
+        for row in rows {
+            let user_id = UserId(row.get::<_, i64>(0) as u64);
+            cache.insert(user_id, None);
+            expired.extend(from_row(user_id, &row));
+        }
+
+        Ok(expired)
+    }
+
+    fn expired(&self, conversation: &Conversation) -> bool {
+        let age = Utc::now() - conversation.updated_at;
+        age.to_std().map(|age| age > self.ttl).unwrap_or(false)
+    }
+
+    async fn load(&self, user_id: UserId) -> anyhow::Result<Option<Conversation>> {
+        let client = self.client().await?;
+        let id = i64::try_from(user_id.0).context("failed to cast user id to i64")?;
+        let row = retry(|| async {
+            client
+                .query_opt(
+                    "select user_id, version, state, chat_id, keyboard_msg_id, updated_at
+                     from conversation_states where user_id = $1",
+                    &[&id],
+                )
+                .await
+        })
+        .await
+        .context("failed to load conversation state")?;
+
+        Ok(row.and_then(|row| from_row(user_id, &row)))
+    }
+}
+
+// states which can not be read (of another version or broken) are dropped
+fn from_row(user_id: UserId, row: &Row) -> Option<Conversation> {
+    let (version, json) = (row.get::<_, i16>(1), row.get::<_, Value>(2));
+    let state = match version {
+        VERSION => ConversationState::from_json(&json),
+        _ => Err(anyhow::anyhow!("unsupported version: {version}")),
+    };
+
+    match state {
+        Ok(state) => Some(Conversation {
+            state,
+            chat_id: ChatId(row.get(3)),
+            keyboard: row.get::<_, Option<i32>>(4).map(MessageId),
+            updated_at: row.get(5),
+        }),
+        Err(err) => {
+            tracing::warn!(user_id = user_id.0, "dropping conversation state: {err:#}");
+            None
+        }
     }
 }
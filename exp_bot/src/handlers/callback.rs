@@ -1,23 +1,46 @@
 use anyhow::Context;
-use chrono::NaiveDate;
+use chrono::{Datelike, NaiveDate, Utc};
 use teloxide_core::{
-    payloads::SendMessageSetters,
+    payloads::{
+        EditMessageMediaSetters, EditMessageReplyMarkupSetters, EditMessageTextSetters,
+        SendMessageSetters, SendPhotoSetters,
+    },
     requests::Requester,
-    types::{InlineKeyboardButton, InlineKeyboardButtonKind, InlineKeyboardMarkup, MessageId},
+    types::{
+        InlineKeyboardButton, InlineKeyboardButtonKind, InlineKeyboardMarkup, InputFile,
+        InputMedia, InputMediaPhoto, MessageId,
+    },
 };
 
-use crate::{ConversationState, ExecCtx, MsgCtx};
+use super::{
+    calendar_keyboard, categories_keyboard, describe_expenses, expense_keyboard, history_keyboard,
+    manage_categories_keyboard, manage_category_keyboard, remove_keyboard, report_keyboard,
+    unrecorded, HISTORY_PER_PAGE, MONTHS,
+};
+use crate::{
+    conversation_state::ExpenseField,
+    report::{self, Chart, Stats},
+    retry::RequestExt,
+    ConversationState, ExecCtx, MsgCtx,
+};
 
 pub(crate) async fn add_category(exec_ctx: &ExecCtx, msg_ctx: &MsgCtx) -> anyhow::Result<()> {
     exec_ctx
         .bot
         .send_message(msg_ctx.chat.id, "please, provide category name")
+        .send_retrying()
         .await
         .context("failed to send message")?;
     exec_ctx
         .cstate
-        .set(msg_ctx.user.id, ConversationState::AwaitingCategoryName)
-        .await;
+        .set(
+            msg_ctx.user.id,
+            msg_ctx.chat.id,
+            ConversationState::AwaitingCategoryName,
+            None,
+        )
+        .await
+        .context("failed to set conversation state")?;
 
     Ok(())
 }
@@ -30,20 +53,28 @@ pub(crate) async fn reject_category_name(
     if let Some(ConversationState::AwaitingCategoryNameConfirmation {
         msg_id: expected_msg_id,
         ..
-    }) = exec_ctx.cstate.get(msg_ctx.user.id).await
+    }) = exec_ctx.cstate.get(msg_ctx.user.id).await?
     {
         if source_msg_id == expected_msg_id {
+            let categories = exec_ctx
+                .storage
+                .categories(&msg_ctx.user)
+                .await
+                .context("failed to load categories")?;
+
             exec_ctx
                 .bot
                 .send_message(msg_ctx.chat.id, "choose category")
-                .reply_markup(InlineKeyboardMarkup::new([[InlineKeyboardButton::new(
-                    "add category",
-                    InlineKeyboardButtonKind::CallbackData("add_category".into()),
-                )]]))
+                .reply_markup(categories_keyboard(&categories, 0))
+                .send_retrying()
                 .await
                 .context("failed to send message")?;
 
-            exec_ctx.cstate.clear(msg_ctx.user.id).await;
+            exec_ctx
+                .cstate
+                .clear(msg_ctx.user.id)
+                .await
+                .context("failed to clear conversation state")?;
         }
     }
 
@@ -58,7 +89,7 @@ pub(crate) async fn confirm_category_name(
     if let Some(ConversationState::AwaitingCategoryNameConfirmation {
         msg_id: expected_msg_id,
         category_name: cname,
-    }) = exec_ctx.cstate.get(msg_ctx.user.id).await
+    }) = exec_ctx.cstate.get(msg_ctx.user.id).await?
     {
         if source_msg_id == expected_msg_id {
             let inserted = exec_ctx
@@ -67,49 +98,276 @@ pub(crate) async fn confirm_category_name(
                 .await
                 .context("failed to add category")?;
 
-            let mut resp = inserted
-                .then(|| format!("category '{cname}' added"))
-                .unwrap_or_else(|| format!("category '{cname}' has already been added"));
+            let resp = if inserted {
+                format!("category '{cname}' added")
+            } else {
+                format!("category '{cname}' has already been added")
+            };
 
-            resp.push_str("\n\nplease, provide expense date");
+            ask_expense_date(&cname, resp, exec_ctx, msg_ctx).await?;
+        }
+    }
+
+    Ok(())
+}
+
+pub(crate) async fn confirm_quick_add(
+    source_msg_id: MessageId,
+    exec_ctx: &ExecCtx,
+    msg_ctx: &MsgCtx,
+) -> anyhow::Result<()> {
+    if let Some(ConversationState::AwaitingQuickAddConfirmation {
+        msg_id: expected_msg_id,
+        category_name: cname,
+        amounts,
+        date,
+    }) = exec_ctx.cstate.get(msg_ctx.user.id).await?
+    {
+        if source_msg_id == expected_msg_id {
+            // the category may have been added in the meantime, which is fine
+            exec_ctx
+                .storage
+                .add_category(&msg_ctx.user, &cname)
+                .await
+                .context("failed to add category")?;
+            exec_ctx
+                .storage
+                .add_expenses(&msg_ctx.user, msg_ctx.chat.id, &cname, &amounts, date)
+                .await
+                .context("failed to add expenses")?;
 
-            let mk_button = |name: &str, data: &str| {
-                InlineKeyboardButton::new(
-                    name,
-                    InlineKeyboardButtonKind::CallbackData(format!(
-                        "ped:{}:{data}",
-                        msg_ctx.msg.id
-                    )),
+            remove_keyboard(exec_ctx, msg_ctx.chat.id, msg_ctx.msg.id).await;
+            exec_ctx
+                .bot
+                .send_message(
+                    msg_ctx.chat.id,
+                    format!(
+                        "category '{cname}' added\n\nrecorded {}",
+                        describe_expenses(&cname, &amounts, date)
+                    ),
                 )
+                .send_retrying()
+                .await
+                .context("failed to send message")?;
+
+            exec_ctx
+                .cstate
+                .clear(msg_ctx.user.id)
+                .await
+                .context("failed to clear conversation state")?;
+        }
+    }
+
+    Ok(())
+}
+
+pub(crate) async fn reject_quick_add(
+    source_msg_id: MessageId,
+    exec_ctx: &ExecCtx,
+    msg_ctx: &MsgCtx,
+) -> anyhow::Result<()> {
+    if let Some(ConversationState::AwaitingQuickAddConfirmation {
+        msg_id: expected_msg_id,
+        ..
+    }) = exec_ctx.cstate.get(msg_ctx.user.id).await?
+    {
+        if source_msg_id == expected_msg_id {
+            remove_keyboard(exec_ctx, msg_ctx.chat.id, msg_ctx.msg.id).await;
+            exec_ctx
+                .bot
+                .send_message(msg_ctx.chat.id, "nothing recorded")
+                .send_retrying()
+                .await
+                .context("failed to send message")?;
+
+            exec_ctx
+                .cstate
+                .clear(msg_ctx.user.id)
+                .await
+                .context("failed to clear conversation state")?;
+        }
+    }
+
+    Ok(())
+}
+
+pub(crate) async fn confirm_import(
+    source_msg_id: MessageId,
+    exec_ctx: &ExecCtx,
+    msg_ctx: &MsgCtx,
+) -> anyhow::Result<()> {
+    if let Some(ConversationState::AwaitingImportConfirmation {
+        msg_id: expected_msg_id,
+        expenses,
+    }) = exec_ctx.cstate.get(msg_ctx.user.id).await?
+    {
+        if source_msg_id == expected_msg_id {
+            // the state has the whole file, so this also covers whatever
+            // has been recorded since the preview was shown
+            let recorded = match expenses.first() {
+                Some((date, ..)) => exec_ctx
+                    .storage
+                    .expenses(&msg_ctx.user, (date.year(), date.month()))
+                    .await
+                    .context("failed to load expenses")?,
+                None => vec![],
             };
+            let expenses = unrecorded(expenses, recorded);
 
+            let imported = exec_ctx
+                .storage
+                .import(&msg_ctx.user, msg_ctx.chat.id, &expenses)
+                .await
+                .context("failed to import expenses")?;
+
+            remove_keyboard(exec_ctx, msg_ctx.chat.id, msg_ctx.msg.id).await;
             exec_ctx
                 .bot
-                .send_message(msg_ctx.chat.id, resp)
-                .reply_markup(InlineKeyboardMarkup::new([
-                    [mk_button("today", "today")],
-                    [mk_button("yesterday", "yesterday")],
-                    // [mk_button("pick date", "custom")],
-                ]))
+                .send_message(msg_ctx.chat.id, format!("imported {imported} expenses"))
+                .send_retrying()
                 .await
                 .context("failed to send message")?;
 
             exec_ctx
                 .cstate
-                .set(
-                    msg_ctx.user.id,
-                    ConversationState::AwaitingExpenseDate {
-                        msg_id: msg_ctx.msg.id,
-                        category_name: cname.to_string(),
-                    },
-                )
-                .await;
+                .clear(msg_ctx.user.id)
+                .await
+                .context("failed to clear conversation state")?;
         }
     }
 
     Ok(())
 }
 
+pub(crate) async fn reject_import(
+    source_msg_id: MessageId,
+    exec_ctx: &ExecCtx,
+    msg_ctx: &MsgCtx,
+) -> anyhow::Result<()> {
+    if let Some(ConversationState::AwaitingImportConfirmation {
+        msg_id: expected_msg_id,
+        ..
+    }) = exec_ctx.cstate.get(msg_ctx.user.id).await?
+    {
+        if source_msg_id == expected_msg_id {
+            remove_keyboard(exec_ctx, msg_ctx.chat.id, msg_ctx.msg.id).await;
+            exec_ctx
+                .bot
+                .send_message(msg_ctx.chat.id, "nothing imported")
+                .send_retrying()
+                .await
+                .context("failed to send message")?;
+
+            exec_ctx
+                .cstate
+                .clear(msg_ctx.user.id)
+                .await
+                .context("failed to clear conversation state")?;
+        }
+    }
+
+    Ok(())
+}
+
+pub(crate) async fn pick_category(
+    category_id: i64,
+    exec_ctx: &ExecCtx,
+    msg_ctx: &MsgCtx,
+) -> anyhow::Result<()> {
+    let cname = exec_ctx
+        .storage
+        .category(&msg_ctx.user, category_id)
+        .await
+        .context("failed to load category")?;
+
+    let Some(cname) = cname else {
+        exec_ctx
+            .bot
+            .send_message(msg_ctx.chat.id, "this category does not exist anymore")
+            .send_retrying()
+            .await
+            .context("failed to send message")?;
+
+        return Ok(());
+    };
+
+    ask_expense_date(&cname, format!("category '{cname}'"), exec_ctx, msg_ctx).await
+}
+
+pub(crate) async fn categories_page(
+    page: usize,
+    exec_ctx: &ExecCtx,
+    msg_ctx: &MsgCtx,
+) -> anyhow::Result<()> {
+    let categories = exec_ctx
+        .storage
+        .categories(&msg_ctx.user)
+        .await
+        .context("failed to load categories")?;
+
+    exec_ctx
+        .bot
+        .edit_message_reply_markup(msg_ctx.chat.id, msg_ctx.msg.id)
+        .reply_markup(categories_keyboard(&categories, page))
+        .send_retrying()
+        .await
+        .context("failed to edit message")?;
+
+    Ok(())
+}
+
+// the date buttons refer to the message they are asked from (`msg_ctx.msg`)
+async fn ask_expense_date(
+    cname: &str,
+    mut resp: String,
+    exec_ctx: &ExecCtx,
+    msg_ctx: &MsgCtx,
+) -> anyhow::Result<()> {
+    resp.push_str("\n\nplease, provide expense date");
+
+    let mk_button = |name: &str, data: &str| {
+        InlineKeyboardButton::new(
+            name,
+            InlineKeyboardButtonKind::CallbackData(format!("ped:{}:{data}", msg_ctx.msg.id)),
+        )
+    };
+
+    let keyboard = exec_ctx
+        .bot
+        .send_message(msg_ctx.chat.id, resp)
+        .reply_markup(InlineKeyboardMarkup::new([
+            [mk_button("today", "today")],
+            [mk_button("yesterday", "yesterday")],
+            [InlineKeyboardButton::new(
+                "pick date",
+                InlineKeyboardButtonKind::CallbackData(format!(
+                    "cal:{}:{}",
+                    msg_ctx.msg.id,
+                    Utc::now().format("%Y%m")
+                )),
+            )],
+        ]))
+        .send_retrying()
+        .await
+        .context("failed to send message")?;
+
+    exec_ctx
+        .cstate
+        .set(
+            msg_ctx.user.id,
+            msg_ctx.chat.id,
+            ConversationState::AwaitingExpenseDate {
+                msg_id: msg_ctx.msg.id,
+                category_name: cname.to_string(),
+            },
+            Some(keyboard.id),
+        )
+        .await
+        .context("failed to set conversation state")?;
+
+    Ok(())
+}
+
 pub(crate) async fn pick_expense_date(
     source_msg_id: MessageId,
     date: NaiveDate,
@@ -119,12 +377,27 @@ pub(crate) async fn pick_expense_date(
     if let Some(ConversationState::AwaitingExpenseDate {
         msg_id: expected_msg_id,
         category_name: cname,
-    }) = exec_ctx.cstate.get(msg_ctx.user.id).await
+    }) = exec_ctx.cstate.get(msg_ctx.user.id).await?
     {
         if source_msg_id == expected_msg_id {
+            if !exec_ctx.allow_future_dates && date > Utc::now().date_naive() {
+                exec_ctx
+                    .bot
+                    .send_message(msg_ctx.chat.id, "expense date can not be in the future")
+                    .send_retrying()
+                    .await
+                    .context("failed to send message")?;
+
+                return Ok(());
+            }
+
             exec_ctx
                 .bot
-                .send_message(msg_ctx.chat.id, "please, provide expense amount")
+                .send_message(
+                    msg_ctx.chat.id,
+                    format!("{date}\n\nplease, provide expense amount"),
+                )
+                .send_retrying()
                 .await
                 .context("failed to send message")?;
 
@@ -132,14 +405,585 @@ pub(crate) async fn pick_expense_date(
                 .cstate
                 .set(
                     msg_ctx.user.id,
+                    msg_ctx.chat.id,
                     ConversationState::AwaitingExpenseAmount {
                         date,
                         category_name: cname.to_string(),
                     },
+                    None,
                 )
-                .await;
+                .await
+                .context("failed to set conversation state")?;
+        }
+    }
+
+    Ok(())
+}
+
+pub(crate) async fn expense_calendar(
+    source_msg_id: MessageId,
+    (year, month): (i32, u32),
+    exec_ctx: &ExecCtx,
+    msg_ctx: &MsgCtx,
+) -> anyhow::Result<()> {
+    let marked = exec_ctx
+        .storage
+        .expenses(&msg_ctx.user, (year, month))
+        .await
+        .context("failed to load expenses")?
+        .into_iter()
+        .map(|(date, _category, _amount)| date.day())
+        .collect();
+
+    exec_ctx
+        .bot
+        .edit_message_reply_markup(msg_ctx.chat.id, msg_ctx.msg.id)
+        .reply_markup(calendar_keyboard(
+            source_msg_id,
+            (year, month),
+            &marked,
+            exec_ctx.allow_future_dates,
+        ))
+        .send_retrying()
+        .await
+        .context("failed to edit message")?;
+
+    Ok(())
+}
+
+pub(crate) async fn report_year(
+    year: i32,
+    exec_ctx: &ExecCtx,
+    msg_ctx: &MsgCtx,
+) -> anyhow::Result<()> {
+    exec_ctx
+        .bot
+        .edit_message_text(msg_ctx.chat.id, msg_ctx.msg.id, year.to_string())
+        .reply_markup(report_keyboard(year))
+        .send_retrying()
+        .await
+        .context("failed to edit message")?;
+
+    Ok(())
+}
+
+pub(crate) async fn report(
+    (year, month): (i32, u32),
+    chart: Chart,
+    exec_ctx: &ExecCtx,
+    msg_ctx: &MsgCtx,
+) -> anyhow::Result<()> {
+    let expenses = exec_ctx
+        .storage
+        .expenses(&msg_ctx.user, (year, month))
+        .await
+        .context("failed to load expenses")?;
+
+    let month_name = format!("{} {year}", MONTHS[month as usize - 1]);
+    let stats = Stats::new((year, month), &expenses);
+    if stats.is_empty() {
+        exec_ctx
+            .bot
+            .send_message(msg_ctx.chat.id, format!("no expenses in {month_name}"))
+            .send_retrying()
+            .await
+            .context("failed to send message")?;
+
+        return Ok(());
+    }
+
+    let caption = format!("{month_name}, total: {:.2}", stats.total());
+    // drawing takes a while, keep it off the async workers
+    let png = tokio::task::spawn_blocking(move || report::render((year, month), &stats, chart))
+        .await
+        .context("failed to join chart rendering")?
+        .context("failed to render chart")?;
+    let photo = InputFile::memory(png).file_name(format!("{year}-{month:02}.png"));
+
+    let other = chart.other();
+    let markup = InlineKeyboardMarkup::new([[InlineKeyboardButton::new(
+        other.name(),
+        InlineKeyboardButtonKind::CallbackData(format!("rpm:{year}:{month}:{}", other.code())),
+    )]]);
+
+    // switching chart type replaces the chart in place
+    if msg_ctx.msg.photo().is_some() {
+        exec_ctx
+            .bot
+            .edit_message_media(
+                msg_ctx.chat.id,
+                msg_ctx.msg.id,
+                InputMedia::Photo(InputMediaPhoto::new(photo).caption(caption)),
+            )
+            .reply_markup(markup)
+            .send_retrying()
+            .await
+            .context("failed to edit message")?;
+    } else {
+        exec_ctx
+            .bot
+            .send_photo(msg_ctx.chat.id, photo)
+            .caption(caption)
+            .reply_markup(markup)
+            .send_retrying()
+            .await
+            .context("failed to send photo")?;
+    }
+
+    Ok(())
+}
+
+// `header` goes above the page, pages left empty by deletions step back
+pub(crate) async fn history_page(
+    mut page: usize,
+    header: Option<String>,
+    exec_ctx: &ExecCtx,
+    msg_ctx: &MsgCtx,
+) -> anyhow::Result<()> {
+    let expenses = loop {
+        let expenses = exec_ctx
+            .storage
+            .history(&msg_ctx.user, page * HISTORY_PER_PAGE, HISTORY_PER_PAGE + 1)
+            .await
+            .context("failed to load expenses")?;
+
+        if !expenses.is_empty() || page == 0 {
+            break expenses;
+        }
+        page -= 1;
+    };
+
+    let mut text = header.map(|header| header + "\n\n").unwrap_or_default();
+    if expenses.is_empty() {
+        text.push_str("no expenses yet");
+        exec_ctx
+            .bot
+            .edit_message_text(msg_ctx.chat.id, msg_ctx.msg.id, text)
+            .send_retrying()
+            .await
+            .context("failed to edit message")?;
+
+        return Ok(());
+    }
+
+    text.push_str("pick an expense to edit or delete");
+    exec_ctx
+        .bot
+        .edit_message_text(msg_ctx.chat.id, msg_ctx.msg.id, text)
+        .reply_markup(history_keyboard(&expenses, page))
+        .send_retrying()
+        .await
+        .context("failed to edit message")?;
+
+    Ok(())
+}
+
+pub(crate) async fn history_expense(
+    expense_id: i64,
+    page: usize,
+    exec_ctx: &ExecCtx,
+    msg_ctx: &MsgCtx,
+) -> anyhow::Result<()> {
+    let expense = exec_ctx
+        .storage
+        .expense(&msg_ctx.user, expense_id)
+        .await
+        .context("failed to load expense")?;
+
+    let Some((date, cname, amount)) = expense else {
+        let header = "this expense does not exist anymore".to_string();
+        return history_page(page, Some(header), exec_ctx, msg_ctx).await;
+    };
+
+    exec_ctx
+        .bot
+        .edit_message_text(
+            msg_ctx.chat.id,
+            msg_ctx.msg.id,
+            describe_expenses(&cname, &[amount], date),
+        )
+        .reply_markup(expense_keyboard(expense_id, page))
+        .send_retrying()
+        .await
+        .context("failed to edit message")?;
+
+    Ok(())
+}
+
+pub(crate) async fn edit_expense(
+    expense_id: i64,
+    field: ExpenseField,
+    exec_ctx: &ExecCtx,
+    msg_ctx: &MsgCtx,
+) -> anyhow::Result<()> {
+    let expense = exec_ctx
+        .storage
+        .expense(&msg_ctx.user, expense_id)
+        .await
+        .context("failed to load expense")?;
+
+    let Some((date, cname, amount)) = expense else {
+        exec_ctx
+            .bot
+            .send_message(msg_ctx.chat.id, "this expense does not exist anymore")
+            .send_retrying()
+            .await
+            .context("failed to send message")?;
+
+        return Ok(());
+    };
+
+    let hint = match field {
+        ExpenseField::Date => " (today, yesterday or 2023-07-14)",
+        ExpenseField::Amount | ExpenseField::Category => "",
+    };
+    exec_ctx
+        .bot
+        .send_message(
+            msg_ctx.chat.id,
+            format!(
+                "{}\n\nplease, provide new {}{hint}",
+                describe_expenses(&cname, &[amount], date),
+                field.name()
+            ),
+        )
+        .send_retrying()
+        .await
+        .context("failed to send message")?;
+
+    exec_ctx
+        .cstate
+        .set(
+            msg_ctx.user.id,
+            msg_ctx.chat.id,
+            ConversationState::EditingExpense { expense_id, field },
+            None,
+        )
+        .await
+        .context("failed to set conversation state")?;
+
+    Ok(())
+}
+
+pub(crate) async fn delete_expense(
+    expense_id: i64,
+    page: usize,
+    exec_ctx: &ExecCtx,
+    msg_ctx: &MsgCtx,
+) -> anyhow::Result<()> {
+    let expense = exec_ctx
+        .storage
+        .delete_expense(&msg_ctx.user, expense_id)
+        .await
+        .context("failed to delete expense")?;
+
+    let header = match expense {
+        Some((date, cname, amount)) => {
+            format!("deleted {}", describe_expenses(&cname, &[amount], date))
         }
+        None => "this expense does not exist anymore".to_string(),
+    };
+
+    history_page(page, Some(header), exec_ctx, msg_ctx).await
+}
+
+// `header` goes above the page
+pub(crate) async fn manage_categories(
+    page: usize,
+    header: Option<String>,
+    exec_ctx: &ExecCtx,
+    msg_ctx: &MsgCtx,
+) -> anyhow::Result<()> {
+    let categories = exec_ctx
+        .storage
+        .all_categories(&msg_ctx.user)
+        .await
+        .context("failed to load categories")?;
+
+    let mut text = header.map(|header| header + "\n\n").unwrap_or_default();
+    if categories.is_empty() {
+        text.push_str("no categories yet");
+        exec_ctx
+            .bot
+            .edit_message_text(msg_ctx.chat.id, msg_ctx.msg.id, text)
+            .send_retrying()
+            .await
+            .context("failed to edit message")?;
+
+        return Ok(());
     }
 
+    text.push_str("pick a category to manage");
+    exec_ctx
+        .bot
+        .edit_message_text(msg_ctx.chat.id, msg_ctx.msg.id, text)
+        .reply_markup(manage_categories_keyboard(&categories, page))
+        .send_retrying()
+        .await
+        .context("failed to edit message")?;
+
     Ok(())
 }
+
+pub(crate) async fn manage_category(
+    category_id: i64,
+    page: usize,
+    exec_ctx: &ExecCtx,
+    msg_ctx: &MsgCtx,
+) -> anyhow::Result<()> {
+    let info = exec_ctx
+        .storage
+        .category_info(&msg_ctx.user, category_id)
+        .await
+        .context("failed to load category")?;
+
+    let Some((cname, archived, expenses)) = info else {
+        let header = "this category does not exist anymore".to_string();
+        return manage_categories(page, Some(header), exec_ctx, msg_ctx).await;
+    };
+
+    let mut text = format!("category '{cname}', {expenses} expenses");
+    if archived {
+        text.push_str("\n\narchived: not offered when adding expenses, but kept in reports");
+    }
+
+    exec_ctx
+        .bot
+        .edit_message_text(msg_ctx.chat.id, msg_ctx.msg.id, text)
+        .reply_markup(manage_category_keyboard(category_id, archived, page))
+        .send_retrying()
+        .await
+        .context("failed to edit message")?;
+
+    Ok(())
+}
+
+pub(crate) async fn rename_category(
+    category_id: i64,
+    exec_ctx: &ExecCtx,
+    msg_ctx: &MsgCtx,
+) -> anyhow::Result<()> {
+    let cname = exec_ctx
+        .storage
+        .category(&msg_ctx.user, category_id)
+        .await
+        .context("failed to load category")?;
+
+    let Some(cname) = cname else {
+        exec_ctx
+            .bot
+            .send_message(msg_ctx.chat.id, "this category does not exist anymore")
+            .send_retrying()
+            .await
+            .context("failed to send message")?;
+
+        return Ok(());
+    };
+
+    exec_ctx
+        .bot
+        .send_message(
+            msg_ctx.chat.id,
+            format!("please, provide new name for category '{cname}'"),
+        )
+        .send_retrying()
+        .await
+        .context("failed to send message")?;
+
+    exec_ctx
+        .cstate
+        .set(
+            msg_ctx.user.id,
+            msg_ctx.chat.id,
+            ConversationState::RenamingCategory { category_id },
+            None,
+        )
+        .await
+        .context("failed to set conversation state")?;
+
+    Ok(())
+}
+
+pub(crate) async fn merge_category(
+    category_id: i64,
+    exec_ctx: &ExecCtx,
+    msg_ctx: &MsgCtx,
+) -> anyhow::Result<()> {
+    let cname = exec_ctx
+        .storage
+        .category(&msg_ctx.user, category_id)
+        .await
+        .context("failed to load category")?;
+
+    let Some(cname) = cname else {
+        exec_ctx
+            .bot
+            .send_message(msg_ctx.chat.id, "this category does not exist anymore")
+            .send_retrying()
+            .await
+            .context("failed to send message")?;
+
+        return Ok(());
+    };
+
+    exec_ctx
+        .bot
+        .send_message(
+            msg_ctx.chat.id,
+            format!(
+                "please, provide the category to move the expenses of '{cname}' to. \
+                 '{cname}' is deleted afterwards"
+            ),
+        )
+        .send_retrying()
+        .await
+        .context("failed to send message")?;
+
+    exec_ctx
+        .cstate
+        .set(
+            msg_ctx.user.id,
+            msg_ctx.chat.id,
+            ConversationState::MergingCategory { category_id },
+            None,
+        )
+        .await
+        .context("failed to set conversation state")?;
+
+    Ok(())
+}
+
+pub(crate) async fn confirm_merge(
+    category_id: i64,
+    into_id: i64,
+    exec_ctx: &ExecCtx,
+    msg_ctx: &MsgCtx,
+) -> anyhow::Result<()> {
+    let cname = exec_ctx
+        .storage
+        .category(&msg_ctx.user, category_id)
+        .await
+        .context("failed to load category")?;
+    let into_cname = exec_ctx
+        .storage
+        .category(&msg_ctx.user, into_id)
+        .await
+        .context("failed to load category")?;
+
+    let moved = exec_ctx
+        .storage
+        .merge_categories(&msg_ctx.user, category_id, into_id)
+        .await
+        .context("failed to merge categories")?;
+
+    let text = match (cname, into_cname, moved) {
+        (Some(cname), Some(into_cname), Some(moved)) => {
+            format!("category '{cname}' merged into '{into_cname}', {moved} expenses moved")
+        }
+        _ => "one of the categories does not exist anymore".to_string(),
+    };
+
+    exec_ctx
+        .bot
+        .edit_message_text(msg_ctx.chat.id, msg_ctx.msg.id, text)
+        .send_retrying()
+        .await
+        .context("failed to edit message")?;
+
+    Ok(())
+}
+
+pub(crate) async fn reject_merge(exec_ctx: &ExecCtx, msg_ctx: &MsgCtx) -> anyhow::Result<()> {
+    exec_ctx
+        .bot
+        .edit_message_text(msg_ctx.chat.id, msg_ctx.msg.id, "nothing merged")
+        .send_retrying()
+        .await
+        .context("failed to edit message")?;
+
+    Ok(())
+}
+
+// archives the category or brings it back
+pub(crate) async fn archive_category(
+    category_id: i64,
+    page: usize,
+    exec_ctx: &ExecCtx,
+    msg_ctx: &MsgCtx,
+) -> anyhow::Result<()> {
+    let info = exec_ctx
+        .storage
+        .category_info(&msg_ctx.user, category_id)
+        .await
+        .context("failed to load category")?;
+
+    if let Some((_cname, archived, _expenses)) = info {
+        exec_ctx
+            .storage
+            .set_category_archived(&msg_ctx.user, category_id, !archived)
+            .await
+            .context("failed to archive category")?;
+    }
+
+    manage_category(category_id, page, exec_ctx, msg_ctx).await
+}
+
+pub(crate) async fn delete_category(
+    category_id: i64,
+    page: usize,
+    exec_ctx: &ExecCtx,
+    msg_ctx: &MsgCtx,
+) -> anyhow::Result<()> {
+    let info = exec_ctx
+        .storage
+        .category_info(&msg_ctx.user, category_id)
+        .await
+        .context("failed to load category")?;
+
+    let Some((cname, _archived, expenses)) = info else {
+        let header = "this category does not exist anymore".to_string();
+        return manage_categories(page, Some(header), exec_ctx, msg_ctx).await;
+    };
+
+    let button = |text: &str, data: String| {
+        InlineKeyboardButton::new(text, InlineKeyboardButtonKind::CallbackData(data))
+    };
+    exec_ctx
+        .bot
+        .edit_message_text(
+            msg_ctx.chat.id,
+            msg_ctx.msg.id,
+            format!("delete category '{cname}'? {expenses} expenses will be deleted along with it"),
+        )
+        .reply_markup(InlineKeyboardMarkup::new([[
+            button("delete", format!("mdy:{category_id}:{page}")),
+            button("« back", format!("mcx:{category_id}:{page}")),
+        ]]))
+        .send_retrying()
+        .await
+        .context("failed to edit message")?;
+
+    Ok(())
+}
+
+pub(crate) async fn confirm_delete_category(
+    category_id: i64,
+    page: usize,
+    exec_ctx: &ExecCtx,
+    msg_ctx: &MsgCtx,
+) -> anyhow::Result<()> {
+    let deleted = exec_ctx
+        .storage
+        .delete_category(&msg_ctx.user, category_id)
+        .await
+        .context("failed to delete category")?;
+
+    let header = match deleted {
+        Some((cname, expenses)) => {
+            format!("deleted category '{cname}' and {expenses} expenses")
+        }
+        None => "this category does not exist anymore".to_string(),
+    };
+
+    manage_categories(page, Some(header), exec_ctx, msg_ctx).await
+}
@@ -1,30 +1,38 @@
+use std::collections::{BTreeSet, HashMap};
+
 use anyhow::Context;
-use chrono::NaiveDate;
-use regex::Regex;
+use chrono::{Datelike, NaiveDate, Utc};
+use exp_core::parse;
 use rust_decimal::Decimal;
 use teloxide_core::{
+    net::Download,
     payloads::SendMessageSetters,
     requests::Requester,
-    types::{InlineKeyboardButton, InlineKeyboardButtonKind, InlineKeyboardMarkup},
+    types::{Document, InlineKeyboardButton, InlineKeyboardButtonKind, InlineKeyboardMarkup},
+};
+
+use super::{
+    categories_keyboard, describe_expenses, history_keyboard, manage_categories_keyboard,
+    remove_keyboard, report_keyboard, unrecorded, HISTORY_PER_PAGE, MONTHS,
+};
+use crate::{
+    conversation_state::ExpenseField,
+    retry::RequestExt,
+    storage::{ExpenseEdit, Rename},
+    ConversationState, ExecCtx, MsgCtx,
 };
 
-use crate::{ConversationState, ExecCtx, MsgCtx};
+// month files are small, anything bigger is not one
+const MAX_IMPORT_FILE_SIZE: u32 = 64 * 1024;
 
 pub(crate) async fn report(exec_ctx: &ExecCtx, msg_ctx: &MsgCtx) -> anyhow::Result<()> {
-    let make_row = |months: [&str; 4]| {
-        months.map(|m| {
-            InlineKeyboardButton::new(m, InlineKeyboardButtonKind::CallbackData(m.to_string()))
-        })
-    };
+    let year = Utc::now().year();
 
     exec_ctx
         .bot
-        .send_message(msg_ctx.chat.id, "2023".to_string())
-        .reply_markup(InlineKeyboardMarkup::new([
-            make_row(["Jan", "Feb", "Mar", "Apr"]),
-            make_row(["May", "Jun", "Jul", "Aug"]),
-            make_row(["Sep", "Oct", "Nov", "Dec"]),
-        ]))
+        .send_message(msg_ctx.chat.id, year.to_string())
+        .reply_markup(report_keyboard(year))
+        .send_retrying()
         .await
         .context("failed to send message")?;
 
@@ -32,13 +40,138 @@ pub(crate) async fn report(exec_ctx: &ExecCtx, msg_ctx: &MsgCtx) -> anyhow::Resu
 }
 
 pub(crate) async fn add_expense(exec_ctx: &ExecCtx, msg_ctx: &MsgCtx) -> anyhow::Result<()> {
+    let categories = exec_ctx
+        .storage
+        .categories(&msg_ctx.user)
+        .await
+        .context("failed to load categories")?;
+
     exec_ctx
         .bot
         .send_message(msg_ctx.chat.id, "choose category")
-        .reply_markup(InlineKeyboardMarkup::new([[InlineKeyboardButton::new(
-            "add category",
-            InlineKeyboardButtonKind::CallbackData("add_category".into()),
-        )]]))
+        .reply_markup(categories_keyboard(&categories, 0))
+        .send_retrying()
+        .await
+        .context("failed to send message")?;
+
+    Ok(())
+}
+
+pub(crate) async fn cancel(exec_ctx: &ExecCtx, msg_ctx: &MsgCtx) -> anyhow::Result<()> {
+    let conversation = exec_ctx
+        .cstate
+        .clear(msg_ctx.user.id)
+        .await
+        .context("failed to clear conversation state")?;
+
+    let resp = match conversation {
+        Some(conversation) => {
+            if let Some(keyboard) = conversation.keyboard {
+                remove_keyboard(exec_ctx, conversation.chat_id, keyboard).await;
+            }
+            "cancelled"
+        }
+        None => "nothing to cancel",
+    };
+
+    exec_ctx
+        .bot
+        .send_message(msg_ctx.chat.id, resp)
+        .send_retrying()
+        .await
+        .context("failed to send message")?;
+
+    Ok(())
+}
+
+pub(crate) async fn history(exec_ctx: &ExecCtx, msg_ctx: &MsgCtx) -> anyhow::Result<()> {
+    let expenses = exec_ctx
+        .storage
+        .history(&msg_ctx.user, 0, HISTORY_PER_PAGE + 1)
+        .await
+        .context("failed to load expenses")?;
+
+    if expenses.is_empty() {
+        exec_ctx
+            .bot
+            .send_message(msg_ctx.chat.id, "no expenses yet")
+            .send_retrying()
+            .await
+            .context("failed to send message")?;
+
+        return Ok(());
+    }
+
+    exec_ctx
+        .bot
+        .send_message(msg_ctx.chat.id, "pick an expense to edit or delete")
+        .reply_markup(history_keyboard(&expenses, 0))
+        .send_retrying()
+        .await
+        .context("failed to send message")?;
+
+    Ok(())
+}
+
+pub(crate) async fn undo(exec_ctx: &ExecCtx, msg_ctx: &MsgCtx) -> anyhow::Result<()> {
+    let mut expenses = exec_ctx
+        .storage
+        .undo(&msg_ctx.user, msg_ctx.chat.id)
+        .await
+        .context("failed to undo expense")?;
+    expenses.sort();
+
+    let resp = match expenses.as_slice() {
+        [] => "nothing to undo".to_string(),
+        // a single quick add (or a single expense)
+        [(date, cname, _), ..] if expenses.iter().all(|(d, c, _)| (d, c) == (date, cname)) => {
+            let amounts = expenses
+                .iter()
+                .map(|(.., amount)| *amount)
+                .collect::<Vec<_>>();
+            format!("removed {}", describe_expenses(cname, &amounts, *date))
+        }
+        // an import
+        _ => format!(
+            "removed {} imported expenses, {} in total",
+            expenses.len(),
+            expenses.iter().map(|(.., amount)| amount).sum::<Decimal>()
+        ),
+    };
+
+    exec_ctx
+        .bot
+        .send_message(msg_ctx.chat.id, resp)
+        .send_retrying()
+        .await
+        .context("failed to send message")?;
+
+    Ok(())
+}
+
+pub(crate) async fn categories(exec_ctx: &ExecCtx, msg_ctx: &MsgCtx) -> anyhow::Result<()> {
+    let categories = exec_ctx
+        .storage
+        .all_categories(&msg_ctx.user)
+        .await
+        .context("failed to load categories")?;
+
+    if categories.is_empty() {
+        exec_ctx
+            .bot
+            .send_message(msg_ctx.chat.id, "no categories yet")
+            .send_retrying()
+            .await
+            .context("failed to send message")?;
+
+        return Ok(());
+    }
+
+    exec_ctx
+        .bot
+        .send_message(msg_ctx.chat.id, "pick a category to manage")
+        .reply_markup(manage_categories_keyboard(&categories, 0))
+        .send_retrying()
         .await
         .context("failed to send message")?;
 
@@ -47,9 +180,32 @@ pub(crate) async fn add_expense(exec_ctx: &ExecCtx, msg_ctx: &MsgCtx) -> anyhow:
 
 pub(crate) async fn category_name(exec_ctx: &ExecCtx, msg_ctx: &MsgCtx) -> anyhow::Result<()> {
     // unwrap: if we got this far then the message definetely contains text
-    let cname = msg_ctx.msg.text().unwrap();
+    let cname = msg_ctx.msg.text().unwrap().trim();
 
-    exec_ctx
+    if cname.is_empty() {
+        exec_ctx
+            .bot
+            .send_message(msg_ctx.chat.id, "category name can not be empty. try again")
+            .send_retrying()
+            .await
+            .context("failed to send message")?;
+
+        return Ok(());
+    }
+
+    // names which differ only in whitespace are the same category in month files,
+    // so such a name picks the existing category (archived ones too) like quick add does
+    let cname = exec_ctx
+        .storage
+        .all_categories(&msg_ctx.user)
+        .await
+        .context("failed to load categories")?
+        .into_iter()
+        .map(|(_id, name, _archived)| name)
+        .find(|name| parse::category_name(name) == parse::category_name(cname))
+        .unwrap_or_else(|| cname.to_string());
+
+    let keyboard = exec_ctx
         .bot
         .send_message(msg_ctx.chat.id, format!("[category confirmation]: {cname}"))
         .reply_markup(InlineKeyboardMarkup::new([[
@@ -62,6 +218,7 @@ pub(crate) async fn category_name(exec_ctx: &ExecCtx, msg_ctx: &MsgCtx) -> anyho
                 InlineKeyboardButtonKind::CallbackData(format!("rcn:{}", msg_ctx.msg.id.0)),
             ),
         ]]))
+        .send_retrying()
         .await
         .context("failed to send message")?;
 
@@ -69,12 +226,15 @@ pub(crate) async fn category_name(exec_ctx: &ExecCtx, msg_ctx: &MsgCtx) -> anyho
         .cstate
         .set(
             msg_ctx.user.id,
+            msg_ctx.chat.id,
             ConversationState::AwaitingCategoryNameConfirmation {
                 msg_id: msg_ctx.msg.id,
-                category_name: cname.to_string(),
+                category_name: cname,
             },
+            Some(keyboard.id),
         )
-        .await;
+        .await
+        .context("failed to set conversation state")?;
 
     Ok(())
 }
@@ -86,37 +246,576 @@ pub(crate) async fn expense_amount(
     date: NaiveDate,
 ) -> anyhow::Result<()> {
     // unwrap: if we got this far then the message definetely contains text
-    let amount = msg_ctx.msg.text().unwrap();
-    let pattern = Regex::new(r"^(0|[^0](\d+)?)([.,]\d{1,2})?$").unwrap();
+    let text = msg_ctx.msg.text().unwrap();
 
-    if !pattern.is_match(amount) {
+    // the same amounts as quick add and editing take
+    let Ok(amount) = parse::bot_amount(text.trim()) else {
         exec_ctx
             .bot
             .send_message(
                 msg_ctx.chat.id,
                 "invalid expense amount. try again".to_string(),
             )
+            .send_retrying()
             .await
             .context("failed to send message")?;
 
         return Ok(());
-    }
-
-    let amount = Decimal::from_str_exact(amount).context("failed to parse expense amount")?;
+    };
 
     exec_ctx
         .storage
-        .add_expense(&msg_ctx.user, cname, amount, date)
+        .add_expense(&msg_ctx.user, msg_ctx.chat.id, cname, amount, date)
         .await
         .context("failed to add expense")?;
 
     exec_ctx
         .bot
         .send_message(msg_ctx.chat.id, "expense added")
+        .send_retrying()
         .await
         .context("failed to send message")?;
 
-    exec_ctx.cstate.clear(msg_ctx.user.id).await;
+    exec_ctx
+        .cstate
+        .clear(msg_ctx.user.id)
+        .await
+        .context("failed to clear conversation state")?;
+
+    Ok(())
+}
+
+// a whole expense in one message (`coffee 3.5`). categories are matched the way
+// they are written in month files, unknown ones are created after a confirmation
+pub(crate) async fn quick_add(
+    exec_ctx: &ExecCtx,
+    msg_ctx: &MsgCtx,
+    cname: &str,
+    amounts: &[Decimal],
+    date: NaiveDate,
+) -> anyhow::Result<()> {
+    if !exec_ctx.allow_future_dates && date > Utc::now().date_naive() {
+        exec_ctx
+            .bot
+            .send_message(msg_ctx.chat.id, "expense date can not be in the future")
+            .send_retrying()
+            .await
+            .context("failed to send message")?;
+
+        return Ok(());
+    }
+
+    // archived ones too, their names are taken all the same
+    let categories = exec_ctx
+        .storage
+        .all_categories(&msg_ctx.user)
+        .await
+        .context("failed to load categories")?;
+    let existing = categories
+        .into_iter()
+        .map(|(_id, name, _archived)| name)
+        .find(|name| parse::category_name(name) == cname);
+
+    if let Some(existing) = existing {
+        exec_ctx
+            .storage
+            .add_expenses(&msg_ctx.user, msg_ctx.chat.id, &existing, amounts, date)
+            .await
+            .context("failed to add expenses")?;
+
+        exec_ctx
+            .bot
+            .send_message(
+                msg_ctx.chat.id,
+                format!("recorded {}", describe_expenses(&existing, amounts, date)),
+            )
+            .send_retrying()
+            .await
+            .context("failed to send message")?;
+
+        return Ok(());
+    }
+
+    let keyboard = exec_ctx
+        .bot
+        .send_message(
+            msg_ctx.chat.id,
+            format!(
+                "[category confirmation]: {cname}\n\nthere is no such category yet. add it and record {}?",
+                describe_expenses(cname, amounts, date)
+            ),
+        )
+        .reply_markup(InlineKeyboardMarkup::new([[
+            InlineKeyboardButton::new(
+                "confirm",
+                InlineKeyboardButtonKind::CallbackData(format!("cqa:{}", msg_ctx.msg.id.0)),
+            ),
+            InlineKeyboardButton::new(
+                "reject",
+                InlineKeyboardButtonKind::CallbackData(format!("rqa:{}", msg_ctx.msg.id.0)),
+            ),
+        ]]))
+        .send_retrying()
+        .await
+        .context("failed to send message")?;
+
+    exec_ctx
+        .cstate
+        .set(
+            msg_ctx.user.id,
+            msg_ctx.chat.id,
+            ConversationState::AwaitingQuickAddConfirmation {
+                msg_id: msg_ctx.msg.id,
+                category_name: cname.to_string(),
+                amounts: amounts.to_vec(),
+                date,
+            },
+            Some(keyboard.id),
+        )
+        .await
+        .context("failed to set conversation state")?;
+
+    Ok(())
+}
+
+// a piece of a month file. nothing is recorded until the user has seen
+// what is going to be and confirmed it
+pub(crate) async fn import(
+    exec_ctx: &ExecCtx,
+    msg_ctx: &MsgCtx,
+    text: &str,
+    (year, month): (i32, u32),
+) -> anyhow::Result<()> {
+    let expenses = match parse::month(text, (year, month), true) {
+        Ok(lines) => lines
+            .into_iter()
+            .flat_map(|(date, category, amounts)| {
+                amounts
+                    .into_iter()
+                    .map(move |amount| (date, category.clone(), amount))
+            })
+            .collect::<Vec<_>>(),
+        Err(err) => {
+            exec_ctx
+                .bot
+                .send_message(msg_ctx.chat.id, format!("failed to read expenses: {err:#}"))
+                .send_retrying()
+                .await
+                .context("failed to send message")?;
+
+            return Ok(());
+        }
+    };
+
+    if expenses.is_empty() {
+        exec_ctx
+            .bot
+            .send_message(msg_ctx.chat.id, "no expenses found")
+            .send_retrying()
+            .await
+            .context("failed to send message")?;
+
+        return Ok(());
+    }
+
+    let today = Utc::now().date_naive();
+    if !exec_ctx.allow_future_dates && expenses.iter().any(|(date, ..)| *date > today) {
+        exec_ctx
+            .bot
+            .send_message(msg_ctx.chat.id, "expense date can not be in the future")
+            .send_retrying()
+            .await
+            .context("failed to send message")?;
+
+        return Ok(());
+    }
+
+    // month files spell categories without whitespace
+    let existing = exec_ctx
+        .storage
+        .all_categories(&msg_ctx.user)
+        .await
+        .context("failed to load categories")?
+        .into_iter()
+        .map(|(_id, name, _archived)| (parse::category_name(&name), name))
+        .collect::<HashMap<_, _>>();
+    let mut added = BTreeSet::new();
+    let expenses = expenses
+        .into_iter()
+        .map(|(date, category, amount)| match existing.get(&category) {
+            Some(name) => (date, name.clone(), amount),
+            None => {
+                added.insert(category.clone());
+                (date, category, amount)
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let recorded = exec_ctx
+        .storage
+        .expenses(&msg_ctx.user, (year, month))
+        .await
+        .context("failed to load expenses")?;
+    // only the preview skips recorded rows, the state keeps the whole
+    // file for `confirm_import` to check against what is recorded by then
+    let preview = unrecorded(expenses.clone(), recorded);
+
+    if preview.is_empty() {
+        exec_ctx
+            .bot
+            .send_message(msg_ctx.chat.id, "everything has already been recorded")
+            .send_retrying()
+            .await
+            .context("failed to send message")?;
+
+        return Ok(());
+    }
+
+    let mut totals = HashMap::<&str, Decimal>::new();
+    for (_date, category, amount) in &preview {
+        *totals.entry(category).or_default() += amount;
+    }
+    let mut totals = totals.into_iter().collect::<Vec<_>>();
+    totals.sort_by(|(a, a_total), (b, b_total)| b_total.cmp(a_total).then(a.cmp(b)));
+
+    let days = preview
+        .iter()
+        .map(|(date, ..)| date)
+        .collect::<BTreeSet<_>>()
+        .len();
+    let mut resp = format!("[import]: {} {year}\n\n", MONTHS[month as usize - 1]);
+    for (category, total) in &totals {
+        resp.push_str(&format!("{category} {total}\n"));
+    }
+    resp.push_str(&format!(
+        "total: {}, {} expenses over {days} days",
+        preview.iter().map(|(.., amount)| amount).sum::<Decimal>(),
+        preview.len()
+    ));
+    if expenses.len() > preview.len() {
+        resp.push_str(&format!(
+            "\n\n{} already recorded, skipped",
+            expenses.len() - preview.len()
+        ));
+    }
+    if !added.is_empty() {
+        resp.push_str(&format!(
+            "\nnew categories: {}",
+            added.into_iter().collect::<Vec<_>>().join(", ")
+        ));
+    }
+
+    let keyboard = exec_ctx
+        .bot
+        .send_message(msg_ctx.chat.id, resp)
+        .reply_markup(InlineKeyboardMarkup::new([[
+            InlineKeyboardButton::new(
+                "confirm",
+                InlineKeyboardButtonKind::CallbackData(format!("cim:{}", msg_ctx.msg.id.0)),
+            ),
+            InlineKeyboardButton::new(
+                "reject",
+                InlineKeyboardButtonKind::CallbackData(format!("rim:{}", msg_ctx.msg.id.0)),
+            ),
+        ]]))
+        .send_retrying()
+        .await
+        .context("failed to send message")?;
+
+    exec_ctx
+        .cstate
+        .set(
+            msg_ctx.user.id,
+            msg_ctx.chat.id,
+            ConversationState::AwaitingImportConfirmation {
+                msg_id: msg_ctx.msg.id,
+                expenses,
+            },
+            Some(keyboard.id),
+        )
+        .await
+        .context("failed to set conversation state")?;
+
+    Ok(())
+}
+
+// an uploaded month file. the month is taken from its name
+// (like `2023-07.txt`), otherwise it is the current one
+pub(crate) async fn import_document(
+    exec_ctx: &ExecCtx,
+    msg_ctx: &MsgCtx,
+    document: &Document,
+) -> anyhow::Result<()> {
+    let file_name = document.file_name.clone().unwrap_or_default();
+    let resp = if !file_name.ends_with(".txt") {
+        Some("only .txt files can be imported")
+    } else if document.file.size > MAX_IMPORT_FILE_SIZE {
+        Some("the file is too big to be imported")
+    } else {
+        None
+    };
+    if let Some(resp) = resp {
+        exec_ctx
+            .bot
+            .send_message(msg_ctx.chat.id, resp)
+            .send_retrying()
+            .await
+            .context("failed to send message")?;
+
+        return Ok(());
+    }
+
+    let file = exec_ctx
+        .bot
+        .get_file(&document.file.id)
+        .send_retrying()
+        .await
+        .context("failed to get file")?;
+    let mut data = vec![];
+    exec_ctx
+        .bot
+        .download_file(&file.path, &mut data)
+        .await
+        .context("failed to download file")?;
+
+    let Ok(text) = String::from_utf8(data) else {
+        exec_ctx
+            .bot
+            .send_message(msg_ctx.chat.id, "the file is not a text file")
+            .send_retrying()
+            .await
+            .context("failed to send message")?;
+
+        return Ok(());
+    };
+
+    let today = Utc::now().date_naive();
+    let month = parse::file_month(&file_name).unwrap_or((today.year(), today.month()));
+    import(exec_ctx, msg_ctx, &text, month).await
+}
+
+pub(crate) async fn expense_edit(
+    exec_ctx: &ExecCtx,
+    msg_ctx: &MsgCtx,
+    expense_id: i64,
+    field: ExpenseField,
+) -> anyhow::Result<()> {
+    // unwrap: if we got this far then the message definetely contains text
+    let text = msg_ctx.msg.text().unwrap();
+
+    // the reason to try again if the reply does not do
+    let edit = match field {
+        ExpenseField::Amount => parse::bot_amount(text)
+            .map(ExpenseEdit::Amount)
+            .map_err(|_| "invalid expense amount. try again"),
+        ExpenseField::Date => match parse::date(text, Utc::now().date_naive()) {
+            Some(date) if !exec_ctx.allow_future_dates && date > Utc::now().date_naive() => {
+                Err("expense date can not be in the future")
+            }
+            Some(date) => Ok(ExpenseEdit::Date(date)),
+            None => Err("invalid date. try again"),
+        },
+        // as shown or as written in month files
+        ExpenseField::Category => exec_ctx
+            .storage
+            .all_categories(&msg_ctx.user)
+            .await
+            .context("failed to load categories")?
+            .into_iter()
+            .find(|(_id, name, _archived)| name == text || parse::category_name(name) == text)
+            .map(|(id, _name, _archived)| ExpenseEdit::Category(id))
+            .ok_or("no such category. try again"),
+    };
+
+    let edit = match edit {
+        Ok(edit) => edit,
+        Err(resp) => {
+            exec_ctx
+                .bot
+                .send_message(msg_ctx.chat.id, resp)
+                .send_retrying()
+                .await
+                .context("failed to send message")?;
+
+            return Ok(());
+        }
+    };
+
+    let expense = exec_ctx
+        .storage
+        .edit_expense(&msg_ctx.user, expense_id, edit)
+        .await
+        .context("failed to edit expense")?;
+
+    let resp = match expense {
+        Some((date, cname, amount)) => {
+            format!("updated {}", describe_expenses(&cname, &[amount], date))
+        }
+        None => "this expense does not exist anymore".to_string(),
+    };
+
+    exec_ctx
+        .bot
+        .send_message(msg_ctx.chat.id, resp)
+        .send_retrying()
+        .await
+        .context("failed to send message")?;
+
+    exec_ctx
+        .cstate
+        .clear(msg_ctx.user.id)
+        .await
+        .context("failed to clear conversation state")?;
+
+    Ok(())
+}
+
+pub(crate) async fn category_rename(
+    exec_ctx: &ExecCtx,
+    msg_ctx: &MsgCtx,
+    category_id: i64,
+) -> anyhow::Result<()> {
+    // unwrap: if we got this far then the message definetely contains text
+    let new_cname = msg_ctx.msg.text().unwrap().trim();
+
+    if new_cname.is_empty() {
+        exec_ctx
+            .bot
+            .send_message(msg_ctx.chat.id, "category name can not be empty. try again")
+            .send_retrying()
+            .await
+            .context("failed to send message")?;
+
+        return Ok(());
+    }
+
+    let info = exec_ctx
+        .storage
+        .category_info(&msg_ctx.user, category_id)
+        .await
+        .context("failed to load category")?;
+    // names which differ only in whitespace are the same category in month files
+    let clash = exec_ctx
+        .storage
+        .all_categories(&msg_ctx.user)
+        .await
+        .context("failed to load categories")?
+        .into_iter()
+        .find(|(id, name, _archived)| {
+            *id != category_id && parse::category_name(name) == parse::category_name(new_cname)
+        })
+        .map(|(_id, name, _archived)| name);
+
+    let resp = match (info, clash) {
+        (Some((cname, ..)), Some(existing)) => {
+            format!("category '{existing}' already exists, merge '{cname}' into it instead")
+        }
+        (Some((cname, ..)), None) => match exec_ctx
+            .storage
+            .rename_category(&msg_ctx.user, category_id, new_cname)
+            .await
+            .context("failed to rename category")?
+        {
+            Rename::Done => format!("category '{cname}' renamed to '{new_cname}'"),
+            Rename::NameTaken => {
+                format!("category '{new_cname}' already exists, merge '{cname}' into it instead")
+            }
+            Rename::Missing => "this category does not exist anymore".to_string(),
+        },
+        (None, _) => "this category does not exist anymore".to_string(),
+    };
+
+    exec_ctx
+        .bot
+        .send_message(msg_ctx.chat.id, resp)
+        .send_retrying()
+        .await
+        .context("failed to send message")?;
+
+    exec_ctx
+        .cstate
+        .clear(msg_ctx.user.id)
+        .await
+        .context("failed to clear conversation state")?;
+
+    Ok(())
+}
+
+// the category to merge into, as shown or as written in month files
+pub(crate) async fn category_merge(
+    exec_ctx: &ExecCtx,
+    msg_ctx: &MsgCtx,
+    category_id: i64,
+) -> anyhow::Result<()> {
+    // unwrap: if we got this far then the message definetely contains text
+    let text = msg_ctx.msg.text().unwrap();
+
+    let categories = exec_ctx
+        .storage
+        .all_categories(&msg_ctx.user)
+        .await
+        .context("failed to load categories")?;
+    let cname = categories
+        .iter()
+        .find(|(id, ..)| *id == category_id)
+        .map(|(_id, name, _archived)| name.clone());
+
+    let Some(cname) = cname else {
+        exec_ctx
+            .bot
+            .send_message(msg_ctx.chat.id, "this category does not exist anymore")
+            .send_retrying()
+            .await
+            .context("failed to send message")?;
+
+        exec_ctx
+            .cstate
+            .clear(msg_ctx.user.id)
+            .await
+            .context("failed to clear conversation state")?;
+
+        return Ok(());
+    };
+
+    let into = categories.into_iter().find(|(id, name, _archived)| {
+        *id != category_id && (name == text || parse::category_name(name) == text)
+    });
+    let Some((into_id, into_cname, _archived)) = into else {
+        exec_ctx
+            .bot
+            .send_message(msg_ctx.chat.id, "no such category. try again")
+            .send_retrying()
+            .await
+            .context("failed to send message")?;
+
+        return Ok(());
+    };
+
+    exec_ctx
+        .bot
+        .send_message(
+            msg_ctx.chat.id,
+            format!("[merge confirmation]: '{cname}' into '{into_cname}'"),
+        )
+        .reply_markup(InlineKeyboardMarkup::new([[
+            InlineKeyboardButton::new(
+                "confirm",
+                InlineKeyboardButtonKind::CallbackData(format!("mmy:{category_id}:{into_id}")),
+            ),
+            InlineKeyboardButton::new(
+                "reject",
+                InlineKeyboardButtonKind::CallbackData("mmn".to_string()),
+            ),
+        ]]))
+        .send_retrying()
+        .await
+        .context("failed to send message")?;
+
+    exec_ctx
+        .cstate
+        .clear(msg_ctx.user.id)
+        .await
+        .context("failed to clear conversation state")?;
 
     Ok(())
 }
@@ -1,2 +1,347 @@
 pub mod callback;
 pub mod message;
+
+use chrono::{Datelike, Days, Months, NaiveDate, Utc};
+use rust_decimal::Decimal;
+
+use std::collections::{BTreeSet, HashMap};
+use teloxide_core::{
+    requests::Requester,
+    types::{
+        ChatId, InlineKeyboardButton, InlineKeyboardButtonKind, InlineKeyboardMarkup, MessageId,
+    },
+};
+
+use crate::{conversation_state::ExpenseField, report::Chart, retry::RequestExt, ExecCtx};
+
+const CATEGORIES_PER_PAGE: usize = 8;
+pub(crate) const HISTORY_PER_PAGE: usize = 8;
+
+pub(crate) const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+// months of the year (a regular chart by default) and
+// a way to go to the previous and to the next year
+pub(crate) fn report_keyboard(year: i32) -> InlineKeyboardMarkup {
+    let button = |text: String, data: String| {
+        InlineKeyboardButton::new(text, InlineKeyboardButtonKind::CallbackData(data))
+    };
+
+    let mut years = vec![button(
+        format!("« {}", year - 1),
+        format!("rpy:{}", year - 1),
+    )];
+    // there is nothing to report in the future
+    if year < Utc::now().year() {
+        years.push(button(
+            format!("{} »", year + 1),
+            format!("rpy:{}", year + 1),
+        ));
+    }
+
+    let mut rows = vec![years];
+    for row in 0..3 {
+        rows.push(
+            (row * 4..row * 4 + 4)
+                .map(|i| {
+                    button(
+                        MONTHS[i].to_string(),
+                        format!("rpm:{year}:{}:{}", i + 1, Chart::Regular.code()),
+                    )
+                })
+                .collect(),
+        );
+    }
+
+    InlineKeyboardMarkup::new(rows)
+}
+
+// the user's categories (most used first, two per row) a page at a time
+// with "add category" at the very end
+pub(crate) fn categories_keyboard(
+    categories: &[(i64, String)],
+    page: usize,
+) -> InlineKeyboardMarkup {
+    let button = |text: String, data: String| {
+        InlineKeyboardButton::new(text, InlineKeyboardButtonKind::CallbackData(data))
+    };
+
+    let pages = categories.len().div_ceil(CATEGORIES_PER_PAGE);
+    // categories may have gone since the keyboard was sent
+    let page = page.min(pages.saturating_sub(1));
+
+    let mut rows = categories
+        .iter()
+        .skip(page * CATEGORIES_PER_PAGE)
+        .take(CATEGORIES_PER_PAGE)
+        .map(|(id, name)| button(name.clone(), format!("pkc:{id}")))
+        .collect::<Vec<_>>()
+        .chunks(2)
+        .map(<[_]>::to_vec)
+        .collect::<Vec<_>>();
+
+    if pages > 1 {
+        let mut nav = vec![];
+        if page > 0 {
+            nav.push(button("«".to_string(), format!("ctp:{}", page - 1)));
+        }
+        if page + 1 < pages {
+            nav.push(button("»".to_string(), format!("ctp:{}", page + 1)));
+        }
+        rows.push(nav);
+    }
+
+    rows.push(vec![button(
+        "add category".to_string(),
+        "add_category".to_string(),
+    )]);
+
+    InlineKeyboardMarkup::new(rows)
+}
+
+// all the user's categories (archived ones last, two per row) a page at a time
+pub(crate) fn manage_categories_keyboard(
+    categories: &[(i64, String, bool)],
+    page: usize,
+) -> InlineKeyboardMarkup {
+    let button = |text: String, data: String| {
+        InlineKeyboardButton::new(text, InlineKeyboardButtonKind::CallbackData(data))
+    };
+
+    let pages = categories.len().div_ceil(CATEGORIES_PER_PAGE);
+    let page = page.min(pages.saturating_sub(1));
+
+    let mut rows = categories
+        .iter()
+        .skip(page * CATEGORIES_PER_PAGE)
+        .take(CATEGORIES_PER_PAGE)
+        .map(|(id, name, archived)| {
+            let text = if *archived {
+                format!("{name} (archived)")
+            } else {
+                name.clone()
+            };
+            button(text, format!("mcx:{id}:{page}"))
+        })
+        .collect::<Vec<_>>()
+        .chunks(2)
+        .map(<[_]>::to_vec)
+        .collect::<Vec<_>>();
+
+    if pages > 1 {
+        let mut nav = vec![];
+        if page > 0 {
+            nav.push(button("«".to_string(), format!("mcp:{}", page - 1)));
+        }
+        if page + 1 < pages {
+            nav.push(button("»".to_string(), format!("mcp:{}", page + 1)));
+        }
+        rows.push(nav);
+    }
+
+    InlineKeyboardMarkup::new(rows)
+}
+
+// what can be done to a category, `page` is the categories page to go back to
+pub(crate) fn manage_category_keyboard(
+    category_id: i64,
+    archived: bool,
+    page: usize,
+) -> InlineKeyboardMarkup {
+    let button = |text: &str, data: String| {
+        InlineKeyboardButton::new(text, InlineKeyboardButtonKind::CallbackData(data))
+    };
+
+    InlineKeyboardMarkup::new([
+        vec![
+            button("rename", format!("mcr:{category_id}")),
+            button("merge", format!("mcm:{category_id}")),
+        ],
+        vec![
+            button(
+                if archived { "unarchive" } else { "archive" },
+                format!("mca:{category_id}:{page}"),
+            ),
+            button("delete", format!("mcd:{category_id}:{page}")),
+        ],
+        vec![button("« back", format!("mcp:{page}"))],
+    ])
+}
+
+// a page of the user's expenses, one per row. `expenses` is expected to hold
+// one more than fits on a page if there is a next page
+pub(crate) fn history_keyboard(
+    expenses: &[(i64, NaiveDate, String, Decimal)],
+    page: usize,
+) -> InlineKeyboardMarkup {
+    let button = |text: String, data: String| {
+        InlineKeyboardButton::new(text, InlineKeyboardButtonKind::CallbackData(data))
+    };
+
+    let mut rows = expenses
+        .iter()
+        .take(HISTORY_PER_PAGE)
+        .map(|(id, date, category, amount)| {
+            vec![button(
+                format!("{date} {category} {amount}"),
+                format!("hsx:{id}:{page}"),
+            )]
+        })
+        .collect::<Vec<_>>();
+
+    let mut nav = vec![];
+    if page > 0 {
+        nav.push(button("«".to_string(), format!("hsp:{}", page - 1)));
+    }
+    if expenses.len() > HISTORY_PER_PAGE {
+        nav.push(button("»".to_string(), format!("hsp:{}", page + 1)));
+    }
+    if !nav.is_empty() {
+        rows.push(nav);
+    }
+
+    InlineKeyboardMarkup::new(rows)
+}
+
+// what can be done to a recorded expense, `page` is the history page to go back to
+pub(crate) fn expense_keyboard(expense_id: i64, page: usize) -> InlineKeyboardMarkup {
+    let button = |text: &str, data: String| {
+        InlineKeyboardButton::new(text, InlineKeyboardButtonKind::CallbackData(data))
+    };
+
+    InlineKeyboardMarkup::new([
+        [
+            ExpenseField::Amount,
+            ExpenseField::Category,
+            ExpenseField::Date,
+        ]
+        .map(|field| button(field.name(), format!("hse:{expense_id}:{}", field.code())))
+        .to_vec(),
+        vec![button("delete", format!("hsd:{expense_id}:{page}"))],
+        vec![button("« back", format!("hsp:{page}"))],
+    ])
+}
+
+// a month of days to pick the expense date from. days which already have expenses
+// are marked, future ones can not be picked unless `allow_future` is set. the picked
+// date goes to `ped:<msg_id>:<yyyymmdd>` like the today and yesterday buttons do
+pub(crate) fn calendar_keyboard(
+    msg_id: MessageId,
+    (year, month): (i32, u32),
+    marked: &BTreeSet<u32>,
+    allow_future: bool,
+) -> InlineKeyboardMarkup {
+    let button = |text: String, data: String| {
+        InlineKeyboardButton::new(text, InlineKeyboardButtonKind::CallbackData(data))
+    };
+    let noop = |text: &str| button(text.to_string(), "noop".to_string());
+
+    let today = Utc::now().date_naive();
+    // unwrap: `year` and `month` come from a valid date
+    let first = NaiveDate::from_ymd_opt(year, month, 1).unwrap();
+    let prev = first - Months::new(1);
+    let next = first + Months::new(1);
+
+    let mut rows = vec![vec![
+        button(
+            "«".to_string(),
+            format!("cal:{}:{}", msg_id.0, prev.format("%Y%m")),
+        ),
+        noop(&format!("{} {year}", MONTHS[month as usize - 1])),
+        if allow_future || next <= today {
+            button(
+                "»".to_string(),
+                format!("cal:{}:{}", msg_id.0, next.format("%Y%m")),
+            )
+        } else {
+            noop(" ")
+        },
+    ]];
+    rows.push(
+        ["Mo", "Tu", "We", "Th", "Fr", "Sa", "Su"]
+            .map(noop)
+            .to_vec(),
+    );
+
+    let mut week = vec![noop(" "); first.weekday().num_days_from_monday() as usize];
+    let mut date = first;
+    while date < next {
+        let day = date.day();
+        week.push(if !allow_future && date > today {
+            noop("·")
+        } else {
+            let text = if marked.contains(&day) {
+                format!("{day}•")
+            } else {
+                day.to_string()
+            };
+            button(text, format!("ped:{}:{}", msg_id.0, date.format("%Y%m%d")))
+        });
+
+        if week.len() == 7 {
+            rows.push(std::mem::take(&mut week));
+        }
+        date = date + Days::new(1);
+    }
+    if !week.is_empty() {
+        week.resize(7, noop(" "));
+        rows.push(week);
+    }
+
+    InlineKeyboardMarkup::new(rows)
+}
+
+// `coffee 3.5 on 2023-07-14`, several amounts are summed up
+pub(crate) fn describe_expenses(cname: &str, amounts: &[Decimal], date: NaiveDate) -> String {
+    let amounts_str = amounts
+        .iter()
+        .map(Decimal::to_string)
+        .collect::<Vec<_>>()
+        .join(" + ");
+
+    match amounts {
+        [_] => format!("{cname} {amounts_str} on {date}"),
+        _ => format!(
+            "{cname} {amounts_str} = {} on {date}",
+            amounts.iter().sum::<Decimal>()
+        ),
+    }
+}
+
+// the expenses which are not recorded yet. each recorded one makes a single
+// identical expense be left out, the same thing may well be bought twice a day
+pub(crate) fn unrecorded(
+    expenses: Vec<(NaiveDate, String, Decimal)>,
+    recorded: Vec<(NaiveDate, String, Decimal)>,
+) -> Vec<(NaiveDate, String, Decimal)> {
+    let mut recorded = recorded
+        .into_iter()
+        .fold(HashMap::new(), |mut counts, expense| {
+            *counts.entry(expense).or_insert(0) += 1;
+            counts
+        });
+
+    expenses
+        .into_iter()
+        .filter(|expense| match recorded.get_mut(expense) {
+            Some(n) if *n > 0 => {
+                *n -= 1;
+                false
+            }
+            _ => true,
+        })
+        .collect()
+}
+
+// best effort: the message may be gone already or too old to be edited
+pub(crate) async fn remove_keyboard(exec_ctx: &ExecCtx, chat_id: ChatId, msg_id: MessageId) {
+    if let Err(err) = exec_ctx
+        .bot
+        .edit_message_reply_markup(chat_id, msg_id)
+        .send_retrying()
+        .await
+    {
+        tracing::warn!(msg_id = msg_id.0, "failed to remove keyboard: {err}");
+    }
+}
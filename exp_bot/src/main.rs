@@ -3,12 +3,18 @@ use conversation_state::{ConversationState, ConversationStates};
 mod command;
 use command::Command;
 mod handlers;
+mod report;
+mod retry;
+use retry::{RequestExt, Transient};
 mod storage;
 use storage::Storage;
 
 use std::{env::var, time::Duration};
 
 use anyhow::Context;
+use chrono::{Datelike, Utc};
+use deadpool_postgres::{Manager, ManagerConfig, Pool as PgPool, RecyclingMethod};
+use exp_core::parse;
 
 use teloxide_core::{
     payloads::GetUpdatesSetters,
@@ -16,13 +22,21 @@ use teloxide_core::{
     types::{AllowedUpdate, Chat, Message, UpdateKind, User},
     Bot,
 };
-use tokio::time::{interval, MissedTickBehavior};
-use tokio_postgres::{Client as PgClient, Config as PgConf, NoTls};
+use tokio::time::{interval, Instant, MissedTickBehavior};
+use tokio_postgres::{Config as PgConf, NoTls};
+
+const DEFAULT_CONVERSATION_TTL_MINUTES: u64 = 30;
+// how often expired conversations are looked for
+const EXPIRY_INTERVAL: Duration = Duration::from_secs(60);
+// updates are handled one at a time, a couple of spare connections is plenty
+const PG_POOL_SIZE: usize = 4;
 
 struct ExecCtx {
     bot: Bot,
     storage: Storage,
     cstate: ConversationStates,
+    // whether expenses can be dated in the future
+    allow_future_dates: bool,
 }
 
 struct MsgCtx {
@@ -44,69 +58,169 @@ async fn main() -> anyhow::Result<()> {
         .parse::<PgConf>()
         .context("failed to parse db connection info")?;
 
-    let (client, conn) = pg_conf
-        .connect(NoTls)
+    // connections are checked before they are handed out and
+    // replaced when closed, so the bot survives db restarts
+    let manager = Manager::from_config(
+        pg_conf,
+        NoTls,
+        ManagerConfig {
+            recycling_method: RecyclingMethod::Fast,
+        },
+    );
+    let pool = PgPool::builder(manager)
+        .max_size(PG_POOL_SIZE)
+        .build()
+        .context("failed to build db pool")?;
+    // the db has to be there at startup at least
+    let _ = pool
+        .get()
         .await
         .context("failed to establish db connection")?;
-    tokio::spawn(async {
-        conn.await.expect("connection closed");
-    });
+
+    let ttl = match var("CONVERSATION_TTL_MINUTES") {
+        Ok(minutes) => minutes
+            .parse::<u64>()
+            .context("failed to parse conversation ttl")?,
+        Err(_) => DEFAULT_CONVERSATION_TTL_MINUTES,
+    };
+
+    let allow_future_dates = match var("ALLOW_FUTURE_DATES") {
+        Ok(allow) => allow
+            .parse::<bool>()
+            .context("failed to parse whether future dates are allowed")?,
+        Err(_) => false,
+    };
 
     let exec_ctx = ExecCtx {
         bot,
-        storage: Storage::new(client),
-        cstate: ConversationStates::default(),
+        storage: Storage::new(pool.clone()),
+        cstate: ConversationStates::new(pool, Duration::from_secs(ttl * 60)),
+        allow_future_dates,
     };
 
     let mut interval = interval(Duration::from_millis(200));
     interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
 
     let mut offset = 0;
+    // failed polls in a row
+    let mut failures = 0;
+    // right away too, states may have gone stale while the bot was down
+    let mut last_expiry = None::<Instant>;
     loop {
         interval.tick().await;
 
-        let updates = exec_ctx
+        if last_expiry.is_none_or(|at| at.elapsed() >= EXPIRY_INTERVAL) {
+            last_expiry = Some(Instant::now());
+            if let Err(err) = expire_conversations(&exec_ctx).await {
+                tracing::error!("failed to expire conversations: {err:#}");
+            }
+        }
+
+        let updates = match exec_ctx
             .bot
             .get_updates()
             .offset(offset)
             .limit(1)
             .allowed_updates(vec![AllowedUpdate::Message, AllowedUpdate::CallbackQuery])
             .await
-            .context("failed to get updates")?;
+        {
+            Ok(updates) => {
+                failures = 0;
+                updates
+            }
+            Err(err) => {
+                let delay = retry::backoff(failures).max(err.retry_after().unwrap_or_default());
+                tracing::warn!(delay = debug(delay), "failed to get updates: {err}");
+                tokio::time::sleep(delay).await;
+                failures += 1;
+                continue;
+            }
+        };
 
         for update in updates {
             tracing::debug!("handling update");
 
+            // advanced up front so that an update which fails
+            // every time is not fetched over and over again
+            if update.id >= offset {
+                offset = update.id + 1;
+            }
+
             let Some(chat) = update.chat().cloned() else { continue; };
             let Some(user) = update.user().cloned() else { continue; };
 
-            match update.kind {
-                UpdateKind::Message(msg) => {
-                    let msg_ctx = MsgCtx { user, chat, msg };
-                    handle_message(&exec_ctx, &msg_ctx)
-                        .await
-                        .context("failed to handle message")?
-                }
-                UpdateKind::CallbackQuery(cb) => {
-                    let Some(msg) = cb.message else { continue; };
-                    let msg_ctx = MsgCtx { user, chat, msg };
-
-                    let Some(cmd) = cb.data else { continue; };
-                    let cmd = cmd
-                        .parse::<Command>()
-                        .context("failed to parse callback data")?;
+            let (update_id, user_id, chat_id) = (update.id, user.id, chat.id);
+            if let Err(err) = handle_update(&exec_ctx, update.kind, user, chat).await {
+                tracing::error!(
+                    update_id,
+                    user_id = user_id.0,
+                    chat_id = chat_id.0,
+                    "failed to handle update: {err:#}"
+                );
 
-                    handle_callback(cmd, &exec_ctx, &msg_ctx)
-                        .await
-                        .context("failed to handle callback")?
+                // best effort, the user should not be left wondering anyway
+                if let Err(err) = exec_ctx
+                    .bot
+                    .send_message(chat_id, "something went wrong, please try again")
+                    .send_retrying()
+                    .await
+                {
+                    tracing::warn!(update_id, "failed to report error to user: {err}");
                 }
-                _ => unreachable!(),
             }
+        }
+    }
+}
 
-            if update.id >= offset {
-                offset = update.id + 1;
-            }
+// tells the users whose conversations have expired and removes their keyboards
+async fn expire_conversations(exec_ctx: &ExecCtx) -> anyhow::Result<()> {
+    let minutes = exec_ctx.cstate.ttl().as_secs() / 60;
+    for conversation in exec_ctx.cstate.expire().await? {
+        if let Some(keyboard) = conversation.keyboard {
+            handlers::remove_keyboard(exec_ctx, conversation.chat_id, keyboard).await;
+        }
+
+        exec_ctx
+            .bot
+            .send_message(
+                conversation.chat_id,
+                format!("no reply for {minutes} minutes, so the current action has been cancelled"),
+            )
+            .send_retrying()
+            .await
+            .context("failed to send message")?;
+    }
+
+    Ok(())
+}
+
+async fn handle_update(
+    exec_ctx: &ExecCtx,
+    kind: UpdateKind,
+    user: User,
+    chat: Chat,
+) -> anyhow::Result<()> {
+    match kind {
+        UpdateKind::Message(msg) => {
+            let msg_ctx = MsgCtx { user, chat, msg };
+            handle_message(exec_ctx, &msg_ctx)
+                .await
+                .context("failed to handle message")
+        }
+        UpdateKind::CallbackQuery(cb) => {
+            let Some(msg) = cb.message else { return Ok(()); };
+            let msg_ctx = MsgCtx { user, chat, msg };
+
+            let Some(cmd) = cb.data else { return Ok(()); };
+            let cmd = cmd
+                .parse::<Command>()
+                .context(format!("failed to parse callback data: {cmd}"))?;
+
+            handle_callback(cmd, exec_ctx, &msg_ctx)
+                .await
+                .context("failed to handle callback")
         }
+        _ => unreachable!(),
     }
 }
 
@@ -124,7 +238,19 @@ async fn handle_message(exec_ctx: &ExecCtx, msg_ctx: &MsgCtx) -> anyhow::Result<
         Some("/add_expense") => {
             handlers::message::add_expense(exec_ctx, msg_ctx).await?;
         }
-        Some(_text) => match exec_ctx.cstate.get(msg_ctx.user.id).await {
+        Some("/cancel") => {
+            handlers::message::cancel(exec_ctx, msg_ctx).await?;
+        }
+        Some("/history") => {
+            handlers::message::history(exec_ctx, msg_ctx).await?;
+        }
+        Some("/undo") => {
+            handlers::message::undo(exec_ctx, msg_ctx).await?;
+        }
+        Some("/categories") => {
+            handlers::message::categories(exec_ctx, msg_ctx).await?;
+        }
+        Some(text) => match exec_ctx.cstate.get(msg_ctx.user.id).await? {
             Some(ConversationState::AwaitingCategoryName) => {
                 handlers::message::category_name(exec_ctx, msg_ctx).await?;
             }
@@ -134,17 +260,61 @@ async fn handle_message(exec_ctx: &ExecCtx, msg_ctx: &MsgCtx) -> anyhow::Result<
             }) => {
                 handlers::message::expense_amount(exec_ctx, msg_ctx, &category_name, date).await?;
             }
+            Some(ConversationState::EditingExpense { expense_id, field }) => {
+                handlers::message::expense_edit(exec_ctx, msg_ctx, expense_id, field).await?;
+            }
+            Some(ConversationState::RenamingCategory { category_id }) => {
+                handlers::message::category_rename(exec_ctx, msg_ctx, category_id).await?;
+            }
+            Some(ConversationState::MergingCategory { category_id }) => {
+                handlers::message::category_merge(exec_ctx, msg_ctx, category_id).await?;
+            }
+            // several lines are a piece of a month file
+            None if text.contains('\n') => {
+                let today = Utc::now().date_naive();
+                handlers::message::import(exec_ctx, msg_ctx, text, (today.year(), today.month()))
+                    .await?;
+            }
+            None => match parse::quick_add(text, Utc::now().date_naive()) {
+                Ok((category_name, amounts, date)) => {
+                    handlers::message::quick_add(exec_ctx, msg_ctx, &category_name, &amounts, date)
+                        .await?;
+                }
+                Err(err) => {
+                    tracing::debug!("not a quick-add line: {err:#}");
+                    exec_ctx
+                        .bot
+                        .delete_message(msg_ctx.chat.id, msg_ctx.msg.id)
+                        .send_retrying()
+                        .await
+                        .context("failed to delete message")?;
+                }
+            },
             _ => {
                 exec_ctx
                     .bot
                     .delete_message(msg_ctx.chat.id, msg_ctx.msg.id)
+                    .send_retrying()
                     .await
                     .context("failed to delete message")?;
             }
         },
-        None => {
-            tracing::warn!("empty message received");
-        }
+        None => match msg_ctx.msg.document() {
+            Some(document) if exec_ctx.cstate.get(msg_ctx.user.id).await?.is_none() => {
+                handlers::message::import_document(exec_ctx, msg_ctx, document).await?;
+            }
+            Some(_document) => {
+                exec_ctx
+                    .bot
+                    .delete_message(msg_ctx.chat.id, msg_ctx.msg.id)
+                    .send_retrying()
+                    .await
+                    .context("failed to delete message")?;
+            }
+            None => {
+                tracing::warn!("empty message received");
+            }
+        },
     }
 
     Ok(())
@@ -171,6 +341,90 @@ async fn handle_callback(cmd: Command, exec_ctx: &ExecCtx, msg_ctx: &MsgCtx) ->
         } => {
             handlers::callback::pick_expense_date(source_msg_id, date, exec_ctx, msg_ctx).await?;
         }
+        Command::ExpenseCalendar {
+            msg_id: source_msg_id,
+            year,
+            month,
+        } => {
+            handlers::callback::expense_calendar(source_msg_id, (year, month), exec_ctx, msg_ctx)
+                .await?;
+        }
+        Command::Noop => {}
+        Command::PickCategory { category_id } => {
+            handlers::callback::pick_category(category_id, exec_ctx, msg_ctx).await?;
+        }
+        Command::CategoriesPage { page } => {
+            handlers::callback::categories_page(page, exec_ctx, msg_ctx).await?;
+        }
+        Command::ReportYear { year } => {
+            handlers::callback::report_year(year, exec_ctx, msg_ctx).await?;
+        }
+        Command::Report { year, month, chart } => {
+            handlers::callback::report((year, month), chart, exec_ctx, msg_ctx).await?;
+        }
+        Command::ConfirmQuickAdd {
+            msg_id: source_msg_id,
+        } => {
+            handlers::callback::confirm_quick_add(source_msg_id, exec_ctx, msg_ctx).await?;
+        }
+        Command::RejectQuickAdd {
+            msg_id: source_msg_id,
+        } => {
+            handlers::callback::reject_quick_add(source_msg_id, exec_ctx, msg_ctx).await?;
+        }
+        Command::ConfirmImport {
+            msg_id: source_msg_id,
+        } => {
+            handlers::callback::confirm_import(source_msg_id, exec_ctx, msg_ctx).await?;
+        }
+        Command::RejectImport {
+            msg_id: source_msg_id,
+        } => {
+            handlers::callback::reject_import(source_msg_id, exec_ctx, msg_ctx).await?;
+        }
+        Command::HistoryPage { page } => {
+            handlers::callback::history_page(page, None, exec_ctx, msg_ctx).await?;
+        }
+        Command::HistoryExpense { expense_id, page } => {
+            handlers::callback::history_expense(expense_id, page, exec_ctx, msg_ctx).await?;
+        }
+        Command::EditExpense { expense_id, field } => {
+            handlers::callback::edit_expense(expense_id, field, exec_ctx, msg_ctx).await?;
+        }
+        Command::DeleteExpense { expense_id, page } => {
+            handlers::callback::delete_expense(expense_id, page, exec_ctx, msg_ctx).await?;
+        }
+        Command::ManageCategories { page } => {
+            handlers::callback::manage_categories(page, None, exec_ctx, msg_ctx).await?;
+        }
+        Command::ManageCategory { category_id, page } => {
+            handlers::callback::manage_category(category_id, page, exec_ctx, msg_ctx).await?;
+        }
+        Command::RenameCategory { category_id } => {
+            handlers::callback::rename_category(category_id, exec_ctx, msg_ctx).await?;
+        }
+        Command::MergeCategory { category_id } => {
+            handlers::callback::merge_category(category_id, exec_ctx, msg_ctx).await?;
+        }
+        Command::ConfirmMerge {
+            category_id,
+            into_id,
+        } => {
+            handlers::callback::confirm_merge(category_id, into_id, exec_ctx, msg_ctx).await?;
+        }
+        Command::RejectMerge => {
+            handlers::callback::reject_merge(exec_ctx, msg_ctx).await?;
+        }
+        Command::ArchiveCategory { category_id, page } => {
+            handlers::callback::archive_category(category_id, page, exec_ctx, msg_ctx).await?;
+        }
+        Command::DeleteCategory { category_id, page } => {
+            handlers::callback::delete_category(category_id, page, exec_ctx, msg_ctx).await?;
+        }
+        Command::ConfirmDeleteCategory { category_id, page } => {
+            handlers::callback::confirm_delete_category(category_id, page, exec_ctx, msg_ctx)
+                .await?;
+        }
     }
     Ok(())
 }
@@ -0,0 +1,150 @@
+use anyhow::{bail, Context};
+use chrono::{Datelike, NaiveDate};
+use exp_core::chart;
+use plotters::prelude::*;
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+
+use std::collections::HashMap;
+
+const WIDTH: u32 = 640;
+const HEIGHT: u32 = 480;
+
+// same charts as `exp_cli` draws (`regular` and `average-by-day`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chart {
+    Regular,
+    AverageByDay,
+}
+
+impl Chart {
+    // as short as possible: callback data is limited to 64 bytes
+    pub fn code(self) -> &'static str {
+        match self {
+            Chart::Regular => "r",
+            Chart::AverageByDay => "a",
+        }
+    }
+
+    pub fn from_code(code: &str) -> anyhow::Result<Self> {
+        match code {
+            "r" => Ok(Chart::Regular),
+            "a" => Ok(Chart::AverageByDay),
+            _ => bail!("unknown chart code: {code}"),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Chart::Regular => "regular",
+            Chart::AverageByDay => "avg by day",
+        }
+    }
+
+    pub fn other(self) -> Self {
+        match self {
+            Chart::Regular => Chart::AverageByDay,
+            Chart::AverageByDay => Chart::Regular,
+        }
+    }
+}
+
+// amounts by category for every day of the month
+pub struct Stats {
+    days: chart::Stats,
+    // most frequent first so that colors are the same from chart to chart
+    ordered_categories: Vec<String>,
+}
+
+impl Stats {
+    pub fn new((year, month): (i32, u32), expenses: &[(NaiveDate, String, Decimal)]) -> Self {
+        let mut days = chart::Stats::new();
+        for day in 1..=31 {
+            if NaiveDate::from_ymd_opt(year, month, day).is_some() {
+                days.insert(day, HashMap::new());
+            }
+        }
+
+        for (date, category, amount) in expenses {
+            // unwrap: `decimal(10, 2)` always fits
+            let amount = amount.to_f32().unwrap();
+            days.entry(date.day())
+                .or_default()
+                .entry(category.clone())
+                .or_default()
+                .push(amount);
+        }
+
+        // how many days a category shows up on, ties are broken by name
+        let mut frequency = HashMap::<&str, usize>::new();
+        for day_stats in days.values() {
+            for category in day_stats.keys() {
+                *frequency.entry(category).or_default() += 1;
+            }
+        }
+        let mut ordered_categories = frequency.into_iter().collect::<Vec<_>>();
+        ordered_categories.sort_by(|(a, a_freq), (b, b_freq)| b_freq.cmp(a_freq).then(a.cmp(b)));
+        let ordered_categories = ordered_categories
+            .into_iter()
+            .map(|(category, _freq)| category.to_string())
+            .collect();
+
+        Self {
+            days,
+            ordered_categories,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ordered_categories.is_empty()
+    }
+
+    pub fn total(&self) -> f32 {
+        chart::totals(&self.days).values().sum()
+    }
+}
+
+// the chart as a png image, drawn by the same code as `exp_cli` uses
+pub fn render((year, month): (i32, u32), stats: &Stats, chart: Chart) -> anyhow::Result<Vec<u8>> {
+    let mut pixels = vec![0; (WIDTH * HEIGHT * 3) as usize];
+    {
+        let canvas = BitMapBackend::with_buffer(&mut pixels, (WIDTH, HEIGHT)).into_drawing_area();
+        canvas.fill(&WHITE)?;
+        let canvas = canvas.margin(10, 10, 10, 10);
+
+        let days = stats.days.clone();
+        match chart {
+            Chart::Regular => {
+                chart::draw_regular(
+                    &canvas,
+                    (year, month),
+                    days,
+                    &stats.ordered_categories,
+                    false,
+                )?;
+            }
+            Chart::AverageByDay => {
+                chart::draw_average_by_day(
+                    &canvas,
+                    (year, month),
+                    days,
+                    &stats.ordered_categories,
+                    false,
+                )?;
+            }
+        }
+
+        canvas.present()?;
+    }
+
+    let mut png = vec![];
+    let mut encoder = png::Encoder::new(&mut png, WIDTH, HEIGHT);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .context("failed to write png header")?
+        .write_image_data(&pixels)
+        .context("failed to encode png")?;
+
+    Ok(png)
+}
@@ -0,0 +1,93 @@
+use std::{future::Future, time::Duration};
+
+use teloxide_core::{
+    requests::{Output, Request},
+    RequestError,
+};
+use tokio_postgres::error::SqlState;
+
+const ATTEMPTS: u32 = 4;
+const BACKOFF: Duration = Duration::from_millis(250);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+// errors which may go away if the very same thing is tried again a bit later
+pub trait Transient {
+    // `None` when retrying is pointless, otherwise the least time to wait before it
+    fn retry_after(&self) -> Option<Duration>;
+}
+
+impl Transient for RequestError {
+    fn retry_after(&self) -> Option<Duration> {
+        match self {
+            RequestError::RetryAfter(duration) => Some(*duration),
+            // a message may have been delivered even if the response got lost,
+            // so only requests which never reached telegram are safe to repeat
+            RequestError::Network(err) if err.is_connect() => Some(Duration::ZERO),
+            _ => None,
+        }
+    }
+}
+
+impl Transient for tokio_postgres::Error {
+    fn retry_after(&self) -> Option<Duration> {
+        // a closed connection never comes back so only the server
+        // saying "not now" is worth another try
+        let code = self.code()?;
+        [
+            SqlState::T_R_SERIALIZATION_FAILURE,
+            SqlState::T_R_DEADLOCK_DETECTED,
+            SqlState::LOCK_NOT_AVAILABLE,
+            SqlState::TOO_MANY_CONNECTIONS,
+            SqlState::CANNOT_CONNECT_NOW,
+        ]
+        .contains(code)
+        .then_some(Duration::ZERO)
+    }
+}
+
+// exponential, starting at `BACKOFF` and capped at `MAX_BACKOFF`
+pub fn backoff(attempt: u32) -> Duration {
+    BACKOFF
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(MAX_BACKOFF)
+}
+
+// runs `f` until it succeeds, fails for good or runs out of attempts
+pub async fn retry<T, E, F, Fut>(mut f: F) -> Result<T, E>
+where
+    E: Transient + std::fmt::Display,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut attempt = 0;
+    loop {
+        let err = match f().await {
+            Ok(value) => return Ok(value),
+            Err(err) => err,
+        };
+
+        let Some(retry_after) = err.retry_after() else {
+            return Err(err);
+        };
+        if attempt + 1 >= ATTEMPTS {
+            return Err(err);
+        }
+
+        let delay = backoff(attempt).max(retry_after);
+        tracing::warn!(attempt, delay = debug(delay), "{err}, retrying");
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+pub trait RequestExt: Request<Err = RequestError> {
+    // sends the request again while telegram can't be connected to or asks to slow down
+    async fn send_retrying(self) -> Result<Output<Self>, RequestError>
+    where
+        Self: Sized,
+    {
+        retry(|| self.send_ref()).await
+    }
+}
+
+impl<R: Request<Err = RequestError>> RequestExt for R {}
@@ -1,29 +1,36 @@
-use crate::PgClient;
+use crate::{retry::retry, PgPool};
 
 use anyhow::Context;
 use chrono::NaiveDate;
+use deadpool_postgres::Object;
 use rust_decimal::Decimal;
-use teloxide_core::types::User;
+use teloxide_core::types::{ChatId, User};
+use tokio_postgres::error::SqlState;
 
 pub struct Storage {
-    client: PgClient,
+    pool: PgPool,
 }
 
 impl Storage {
-    pub fn new(client: PgClient) -> Self {
-        Self { client }
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    // broken connections are replaced by the pool, so a restart of
+    // the db only fails the requests which were running at the time
+    async fn client(&self) -> anyhow::Result<Object> {
+        self.pool.get().await.context("failed to get db connection")
     }
 
     pub async fn ensure_exists(&self, user: &User) -> anyhow::Result<()> {
-        let stmt = self
-            .client
+        let client = self.client().await?;
+        let stmt = client
             .prepare("insert into users(id) values($1) on conflict do nothing")
             .await
             .context("failed to prepare query")?;
 
         let user_id = i64::try_from(user.id.0).context("failed to cast user id to i64")?;
-        self.client
-            .execute(&stmt, &[&user_id])
+        retry(|| async { client.execute(&stmt, &[&user_id]).await })
             .await
             .context("failed to execute statement")?;
 
@@ -31,8 +38,8 @@ impl Storage {
     }
 
     pub async fn add_category(&self, user: &User, cname: &str) -> anyhow::Result<bool> {
-        let stmt = self
-            .client
+        let client = self.client().await?;
+        let stmt = client
             .prepare(
                 "insert into categories(user_id, category) values($1, $2) on conflict do nothing",
             )
@@ -40,9 +47,7 @@ impl Storage {
             .context("failed to prepare query")?;
 
         let user_id = i64::try_from(user.id.0).context("failed to cast user id to i64")?;
-        let nmod = self
-            .client
-            .execute(&stmt, &[&user_id, &cname])
+        let nmod = retry(|| async { client.execute(&stmt, &[&user_id, &cname]).await })
             .await
             .context("failed to execute statement")?;
 
@@ -52,26 +57,541 @@ impl Storage {
     pub async fn add_expense(
         &self,
         user: &User,
+        chat_id: ChatId,
         cname: &str,
         amount: Decimal,
         date: NaiveDate,
     ) -> anyhow::Result<bool> {
-        let stmt = self
-            .client
+        let client = self.client().await?;
+        let stmt = client
+            .prepare(
+                "insert into expenses(user_id, category_id, amount, date, chat_id, batch_id)
+             values(
+                 $1, (select id from categories where user_id = $1 and category = $2), $3, $4, $5,
+                 nextval('expense_batches')
+             )",
+            )
+            .await
+            .context("failed to prepare query")?;
+
+        let user_id = i64::try_from(user.id.0).context("failed to cast user id to i64")?;
+        let nmod = retry(|| async {
+            client
+                .execute(&stmt, &[&user_id, &cname, &amount, &date, &chat_id.0])
+                .await
+        })
+        .await
+        .context("failed to execute statement")?;
+
+        Ok(nmod > 0)
+    }
+
+    // all the amounts or none of them
+    pub async fn add_expenses(
+        &self,
+        user: &User,
+        chat_id: ChatId,
+        cname: &str,
+        amounts: &[Decimal],
+        date: NaiveDate,
+    ) -> anyhow::Result<u64> {
+        let client = self.client().await?;
+        let stmt = client
             .prepare(
-                "insert into expenses(user_id, category_id, amount, date)
-             values($1, (select id from categories where category = $2), $3, $4)",
+                "with batch as (select nextval('expense_batches') as id)
+             insert into expenses(user_id, category_id, amount, date, chat_id, batch_id)
+             select $1, (select id from categories where user_id = $1 and category = $2), amount, $4, $5, batch.id
+             from unnest($3::decimal[]) as amount, batch",
             )
             .await
             .context("failed to prepare query")?;
 
         let user_id = i64::try_from(user.id.0).context("failed to cast user id to i64")?;
-        let nmod = self
-            .client
-            .execute(&stmt, &[&user_id, &cname, &amount, &date])
+        let nmod = retry(|| async {
+            client
+                .execute(&stmt, &[&user_id, &cname, &amounts, &date, &chat_id.0])
+                .await
+        })
+        .await
+        .context("failed to execute statement")?;
+
+        Ok(nmod)
+    }
+
+    // (date, category, amount) rows, missing categories are created. it is a single
+    // statement, so either everything is inserted or nothing is
+    pub async fn import(
+        &self,
+        user: &User,
+        chat_id: ChatId,
+        expenses: &[(NaiveDate, String, Decimal)],
+    ) -> anyhow::Result<u64> {
+        let client = self.client().await?;
+        let stmt = client
+            .prepare(
+                "with rows as (
+                 select * from unnest($2::date[], $3::text[], $4::decimal[]) as r(date, category, amount)
+             ), added as (
+                 insert into categories(user_id, category)
+                 select distinct $1::bigint, category from rows
+                 on conflict do nothing
+                 returning id, category
+             ), user_categories as (
+                 select id, category from added
+                 union all
+                 select id, category from categories where user_id = $1
+             ), batch as (
+                 select nextval('expense_batches') as id
+             )
+             insert into expenses(user_id, category_id, amount, date, chat_id, batch_id)
+             select $1, c.id, r.amount, r.date, $5, batch.id from rows r
+             join user_categories c on c.category = r.category
+             cross join batch",
+            )
             .await
-            .context("failed to execute statement")?;
+            .context("failed to prepare query")?;
+
+        let user_id = i64::try_from(user.id.0).context("failed to cast user id to i64")?;
+        let dates = expenses.iter().map(|row| row.0).collect::<Vec<_>>();
+        let categories = expenses
+            .iter()
+            .map(|row| row.1.as_str())
+            .collect::<Vec<_>>();
+        let amounts = expenses.iter().map(|row| row.2).collect::<Vec<_>>();
+        let nmod = retry(|| async {
+            client
+                .execute(
+                    &stmt,
+                    &[&user_id, &dates, &categories, &amounts, &chat_id.0],
+                )
+                .await
+        })
+        .await
+        .context("failed to execute statement")?;
+
+        Ok(nmod)
+    }
+
+    // (date, category, amount) of the user's expenses in the month
+    pub async fn expenses(
+        &self,
+        user: &User,
+        (year, month): (i32, u32),
+    ) -> anyhow::Result<Vec<(NaiveDate, String, Decimal)>> {
+        let client = self.client().await?;
+        let (next_year, next_month) = match month {
+            12 => (year + 1, 1),
+            _ => (year, month + 1),
+        };
+        let first = NaiveDate::from_ymd_opt(year, month, 1).context("invalid month")?;
+        let next = NaiveDate::from_ymd_opt(next_year, next_month, 1).context("invalid month")?;
+
+        let stmt = client
+            .prepare(
+                "select e.date, c.category, e.amount from expenses e
+             join categories c on c.id = e.category_id
+             where e.user_id = $1 and e.date >= $2 and e.date < $3
+             order by e.date, e.id",
+            )
+            .await
+            .context("failed to prepare query")?;
+
+        let user_id = i64::try_from(user.id.0).context("failed to cast user id to i64")?;
+        let rows = retry(|| async { client.query(&stmt, &[&user_id, &first, &next]).await })
+            .await
+            .context("failed to execute query")?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.get(0), row.get(1), row.get(2)))
+            .collect())
+    }
+
+    // (id, name) of the categories to pick from, the most used (by number of days) first
+    pub async fn categories(&self, user: &User) -> anyhow::Result<Vec<(i64, String)>> {
+        let client = self.client().await?;
+        let stmt = client
+            .prepare(
+                "select c.id, c.category from categories c
+             left join expenses e on e.category_id = c.id
+             where c.user_id = $1 and not c.archived
+             group by c.id
+             order by count(distinct e.date) desc, c.category",
+            )
+            .await
+            .context("failed to prepare query")?;
+
+        let user_id = i64::try_from(user.id.0).context("failed to cast user id to i64")?;
+        let rows = retry(|| async { client.query(&stmt, &[&user_id]).await })
+            .await
+            .context("failed to execute query")?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.get(0), row.get(1)))
+            .collect())
+    }
+
+    // (id, name, archived) of all the user's categories, archived ones last
+    pub async fn all_categories(&self, user: &User) -> anyhow::Result<Vec<(i64, String, bool)>> {
+        let client = self.client().await?;
+        let stmt = client
+            .prepare(
+                "select c.id, c.category, c.archived from categories c
+             left join expenses e on e.category_id = c.id
+             where c.user_id = $1
+             group by c.id
+             order by c.archived, count(distinct e.date) desc, c.category",
+            )
+            .await
+            .context("failed to prepare query")?;
+
+        let user_id = i64::try_from(user.id.0).context("failed to cast user id to i64")?;
+        let rows = retry(|| async { client.query(&stmt, &[&user_id]).await })
+            .await
+            .context("failed to execute query")?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.get(0), row.get(1), row.get(2)))
+            .collect())
+    }
+
+    // (name, archived, number of expenses)
+    pub async fn category_info(
+        &self,
+        user: &User,
+        category_id: i64,
+    ) -> anyhow::Result<Option<(String, bool, i64)>> {
+        let client = self.client().await?;
+        let stmt = client
+            .prepare(
+                "select c.category, c.archived, count(e.id) from categories c
+             left join expenses e on e.category_id = c.id
+             where c.user_id = $1 and c.id = $2
+             group by c.id",
+            )
+            .await
+            .context("failed to prepare query")?;
+
+        let user_id = i64::try_from(user.id.0).context("failed to cast user id to i64")?;
+        let row = retry(|| async { client.query_opt(&stmt, &[&user_id, &category_id]).await })
+            .await
+            .context("failed to execute query")?;
+
+        Ok(row.map(|row| (row.get(0), row.get(1), row.get(2))))
+    }
+
+    pub async fn rename_category(
+        &self,
+        user: &User,
+        category_id: i64,
+        cname: &str,
+    ) -> anyhow::Result<Rename> {
+        let client = self.client().await?;
+        let stmt = client
+            .prepare("update categories set category = $3 where user_id = $1 and id = $2")
+            .await
+            .context("failed to prepare query")?;
+
+        let user_id = i64::try_from(user.id.0).context("failed to cast user id to i64")?;
+        let nmod = retry(|| async {
+            client
+                .execute(&stmt, &[&user_id, &category_id, &cname])
+                .await
+        })
+        .await;
+
+        match nmod {
+            Ok(0) => Ok(Rename::Missing),
+            Ok(_) => Ok(Rename::Done),
+            // `unique_user_category`
+            Err(err) if err.code() == Some(&SqlState::UNIQUE_VIOLATION) => Ok(Rename::NameTaken),
+            Err(err) => Err(err).context("failed to execute statement"),
+        }
+    }
+
+    // moves the expenses of one category to another and deletes the first one.
+    // the number of moved expenses, if both categories are the user's
+    pub async fn merge_categories(
+        &self,
+        user: &User,
+        category_id: i64,
+        into_id: i64,
+    ) -> anyhow::Result<Option<i64>> {
+        let client = self.client().await?;
+        let stmt = client
+            .prepare(
+                "with target as (
+                 select id from categories where user_id = $1 and id = $3 and id <> $2
+             ), moved as (
+                 update expenses set category_id = $3
+                 where user_id = $1 and category_id = $2 and exists (select from target)
+                 returning id
+             ), deleted as (
+                 delete from categories
+                 where user_id = $1 and id = $2 and exists (select from target)
+                 returning id
+             )
+             select (select count(*) from moved), (select count(*) from deleted)",
+            )
+            .await
+            .context("failed to prepare query")?;
+
+        let user_id = i64::try_from(user.id.0).context("failed to cast user id to i64")?;
+        let row = retry(|| async {
+            client
+                .query_one(&stmt, &[&user_id, &category_id, &into_id])
+                .await
+        })
+        .await
+        .context("failed to execute statement")?;
+
+        let (moved, deleted) = (row.get::<_, i64>(0), row.get::<_, i64>(1));
+        Ok((deleted > 0).then_some(moved))
+    }
+
+    // archived categories are not offered to pick from, but their expenses stay
+    pub async fn set_category_archived(
+        &self,
+        user: &User,
+        category_id: i64,
+        archived: bool,
+    ) -> anyhow::Result<bool> {
+        let client = self.client().await?;
+        let stmt = client
+            .prepare("update categories set archived = $3 where user_id = $1 and id = $2")
+            .await
+            .context("failed to prepare query")?;
+
+        let user_id = i64::try_from(user.id.0).context("failed to cast user id to i64")?;
+        let nmod = retry(|| async {
+            client
+                .execute(&stmt, &[&user_id, &category_id, &archived])
+                .await
+        })
+        .await
+        .context("failed to execute statement")?;
 
         Ok(nmod > 0)
     }
+
+    // the name of the deleted category and the number of expenses deleted along with it
+    pub async fn delete_category(
+        &self,
+        user: &User,
+        category_id: i64,
+    ) -> anyhow::Result<Option<(String, i64)>> {
+        let client = self.client().await?;
+        let stmt = client
+            .prepare(
+                "delete from categories c where user_id = $1 and id = $2
+             returning c.category, (select count(*) from expenses where category_id = c.id)",
+            )
+            .await
+            .context("failed to prepare query")?;
+
+        let user_id = i64::try_from(user.id.0).context("failed to cast user id to i64")?;
+        let row = retry(|| async { client.query_opt(&stmt, &[&user_id, &category_id]).await })
+            .await
+            .context("failed to execute statement")?;
+
+        Ok(row.map(|row| (row.get(0), row.get(1))))
+    }
+
+    pub async fn category(&self, user: &User, category_id: i64) -> anyhow::Result<Option<String>> {
+        let client = self.client().await?;
+        let stmt = client
+            .prepare("select category from categories where user_id = $1 and id = $2")
+            .await
+            .context("failed to prepare query")?;
+
+        let user_id = i64::try_from(user.id.0).context("failed to cast user id to i64")?;
+        let row = retry(|| async { client.query_opt(&stmt, &[&user_id, &category_id]).await })
+            .await
+            .context("failed to execute query")?;
+
+        Ok(row.map(|row| row.get(0)))
+    }
+
+    // (id, date, category, amount) of the user's expenses, the latest first
+    pub async fn history(
+        &self,
+        user: &User,
+        offset: usize,
+        limit: usize,
+    ) -> anyhow::Result<Vec<(i64, NaiveDate, String, Decimal)>> {
+        let client = self.client().await?;
+        let stmt = client
+            .prepare(
+                "select e.id, e.date, c.category, e.amount from expenses e
+             join categories c on c.id = e.category_id
+             where e.user_id = $1
+             order by e.date desc, e.id desc
+             offset $2 limit $3",
+            )
+            .await
+            .context("failed to prepare query")?;
+
+        let user_id = i64::try_from(user.id.0).context("failed to cast user id to i64")?;
+        let offset = i64::try_from(offset).context("failed to cast offset to i64")?;
+        let limit = i64::try_from(limit).context("failed to cast limit to i64")?;
+        let rows = retry(|| async { client.query(&stmt, &[&user_id, &offset, &limit]).await })
+            .await
+            .context("failed to execute query")?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.get(0), row.get(1), row.get(2), row.get(3)))
+            .collect())
+    }
+
+    pub async fn expense(
+        &self,
+        user: &User,
+        expense_id: i64,
+    ) -> anyhow::Result<Option<(NaiveDate, String, Decimal)>> {
+        let client = self.client().await?;
+        let stmt = client
+            .prepare(
+                "select e.date, c.category, e.amount from expenses e
+             join categories c on c.id = e.category_id
+             where e.user_id = $1 and e.id = $2",
+            )
+            .await
+            .context("failed to prepare query")?;
+
+        let user_id = i64::try_from(user.id.0).context("failed to cast user id to i64")?;
+        let row = retry(|| async { client.query_opt(&stmt, &[&user_id, &expense_id]).await })
+            .await
+            .context("failed to execute query")?;
+
+        Ok(row.map(|row| (row.get(0), row.get(1), row.get(2))))
+    }
+
+    // the edited expense, if the user has one with the id
+    pub async fn edit_expense(
+        &self,
+        user: &User,
+        expense_id: i64,
+        edit: ExpenseEdit,
+    ) -> anyhow::Result<Option<(NaiveDate, String, Decimal)>> {
+        let client = self.client().await?;
+        let set = match edit {
+            ExpenseEdit::Amount(_) => "amount = $3",
+            ExpenseEdit::Date(_) => "date = $3",
+            // the category must be the user's own as well
+            ExpenseEdit::Category(_) => {
+                "category_id = (select id from categories where user_id = $1 and id = $3)"
+            }
+        };
+        let stmt = client
+            .prepare(&format!(
+                "with edited as (
+                 update expenses set {set} where user_id = $1 and id = $2
+                 returning date, category_id, amount
+             )
+             select e.date, c.category, e.amount from edited e
+             join categories c on c.id = e.category_id"
+            ))
+            .await
+            .context("failed to prepare query")?;
+
+        let user_id = i64::try_from(user.id.0).context("failed to cast user id to i64")?;
+        let row = retry(|| async {
+            match &edit {
+                ExpenseEdit::Amount(amount) => {
+                    client
+                        .query_opt(&stmt, &[&user_id, &expense_id, amount])
+                        .await
+                }
+                ExpenseEdit::Date(date) => {
+                    client
+                        .query_opt(&stmt, &[&user_id, &expense_id, date])
+                        .await
+                }
+                ExpenseEdit::Category(category_id) => {
+                    client
+                        .query_opt(&stmt, &[&user_id, &expense_id, category_id])
+                        .await
+                }
+            }
+        })
+        .await
+        .context("failed to execute statement")?;
+
+        Ok(row.map(|row| (row.get(0), row.get(1), row.get(2))))
+    }
+
+    // the deleted expense, if the user has one with the id
+    pub async fn delete_expense(
+        &self,
+        user: &User,
+        expense_id: i64,
+    ) -> anyhow::Result<Option<(NaiveDate, String, Decimal)>> {
+        let client = self.client().await?;
+        let stmt = client
+            .prepare(
+                "delete from expenses e using categories c
+             where e.user_id = $1 and e.id = $2 and c.id = e.category_id
+             returning e.date, c.category, e.amount",
+            )
+            .await
+            .context("failed to prepare query")?;
+
+        let user_id = i64::try_from(user.id.0).context("failed to cast user id to i64")?;
+        let row = retry(|| async { client.query_opt(&stmt, &[&user_id, &expense_id]).await })
+            .await
+            .context("failed to execute statement")?;
+
+        Ok(row.map(|row| (row.get(0), row.get(1), row.get(2))))
+    }
+
+    // deletes whatever the user has recorded last in the chat: every amount of a
+    // quick add or every row of an import. expenses recorded before batches
+    // existed are undone one by one
+    pub async fn undo(
+        &self,
+        user: &User,
+        chat_id: ChatId,
+    ) -> anyhow::Result<Vec<(NaiveDate, String, Decimal)>> {
+        let client = self.client().await?;
+        let stmt = client
+            .prepare(
+                "with last as (
+                 select id, batch_id from expenses
+                 where user_id = $1 and chat_id = $2
+                 order by id desc limit 1
+             )
+             delete from expenses e using categories c, last
+             where e.user_id = $1 and (e.id = last.id or e.batch_id = last.batch_id)
+             and c.id = e.category_id
+             returning e.date, c.category, e.amount",
+            )
+            .await
+            .context("failed to prepare query")?;
+
+        let user_id = i64::try_from(user.id.0).context("failed to cast user id to i64")?;
+        let rows = retry(|| async { client.query(&stmt, &[&user_id, &chat_id.0]).await })
+            .await
+            .context("failed to execute statement")?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.get(0), row.get(1), row.get(2)))
+            .collect())
+    }
+}
+
+pub enum ExpenseEdit {
+    Amount(Decimal),
+    Date(NaiveDate),
+    Category(i64),
+}
+
+pub enum Rename {
+    Done,
+    // `unique_user_category` would be violated
+    NameTaken,
+    Missing,
 }
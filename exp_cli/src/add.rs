@@ -0,0 +1,52 @@
+use anyhow::{bail, Context};
+use chrono::{Datelike, NaiveDate, Utc};
+use exp_core::parse;
+use rust_decimal::Decimal;
+
+use std::path::PathBuf;
+
+use crate::{month_file::MonthFile, month_file_path};
+
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    /// `2023-07-21`. today when omitted
+    #[clap(long, value_parser = parse_date)]
+    date: Option<NaiveDate>,
+    /// directory with month files (named like `jul-2023`)
+    #[clap(short, long, default_value = ".")]
+    dir: PathBuf,
+    category: String,
+    /// the same amounts as in month files
+    #[clap(required = true, allow_negative_numbers = true, value_parser = parse::amount)]
+    amounts: Vec<Decimal>,
+}
+
+fn parse_date(raw: &str) -> anyhow::Result<NaiveDate> {
+    NaiveDate::parse_from_str(raw, "%Y-%m-%d").context("failed to parse date")
+}
+
+pub fn run(args: Args) -> anyhow::Result<()> {
+    if args.category.starts_with('#') || args.category.parse::<f32>().is_ok() {
+        bail!("invalid category: {}", args.category);
+    }
+
+    let date = args.date.unwrap_or_else(|| Utc::now().date_naive());
+    let (year, month) = (date.year(), date.month());
+
+    let path = month_file_path(&args.dir, (year, month));
+    let mut file = MonthFile::load(&path).context(format!("failed to load {}", path.display()))?;
+
+    // the file has to be fine before the change as well, otherwise the error
+    // would look like it is caused by the new entry
+    file.validate((year, month))
+        .context(format!("{} is invalid", path.display()))?;
+
+    file.add(date.day(), &args.category, &args.amounts);
+    file.validate((year, month))
+        .context("refusing to write an invalid entry")?;
+
+    file.save(&path)
+        .context(format!("failed to save {}", path.display()))?;
+
+    Ok(())
+}
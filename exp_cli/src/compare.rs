@@ -0,0 +1,259 @@
+use anyhow::Context;
+use chrono::Month;
+use itertools::Itertools;
+use plotters::{
+    prelude::*,
+    style::text_anchor::{HPos, Pos, VPos},
+};
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use crate::{calculate, chart, db, draw_filter_note, filter::Filter, infer_month, totals};
+
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    #[clap(short, long)]
+    output: PathBuf,
+    /// how many of the biggest movers to print
+    #[clap(short, long, default_value = "5")]
+    top: usize,
+    #[command(flatten)]
+    filter: Filter,
+    #[command(flatten)]
+    db: db::Source,
+    /// month files named like `jul-2023` (or just month names with --db). the first one is the baseline
+    #[clap(required = true, num_args = 2..)]
+    data_files: Vec<PathBuf>,
+}
+
+const SLOT: u32 = 1000;
+
+struct Period {
+    label: String,
+    totals: HashMap<String, f32>,
+}
+
+pub fn run(args: Args) -> anyhow::Result<()> {
+    let mut periods = vec![];
+    let mut category_frequency = HashMap::<String, usize>::new();
+    let mut excluded = vec![];
+
+    let months = args
+        .data_files
+        .iter()
+        .map(|data_file| infer_month(data_file))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    // all the months are read from the db at once
+    let texts = match (&args.db.db, args.db.user) {
+        (Some(url), Some(user)) => Some(db::Db::connect(url)?.month_texts(user, &months)?),
+        _ => None,
+    };
+
+    for (data_file, (year, month)) in args.data_files.into_iter().zip(months) {
+        let data = match &texts {
+            Some(texts) => texts.get(&(year, month)).cloned().unwrap_or_default(),
+            None => fs_err::read_to_string(&data_file).context("failed to open data file")?,
+        };
+
+        let (mut stats, _ordered_categories) = calculate(&data, (year, month))
+            .context(format!("failed to calculate ({})", data_file.display()))?;
+        excluded.extend(
+            args.filter
+                .apply(&mut stats)
+                .context("failed to filter categories")?,
+        );
+
+        for day_stats in stats.values() {
+            for category in day_stats.keys() {
+                *category_frequency.entry(category.clone()).or_default() += 1;
+            }
+        }
+
+        // unwrap: `infer_month` only returns valid months
+        let name = Month::try_from(month as u8).unwrap().name();
+        periods.push(Period {
+            label: format!("{} {year}", &name[..3]),
+            totals: totals(&stats),
+        });
+    }
+
+    let ordered_categories = category_frequency
+        .into_iter()
+        .sorted_by(|(a, a_freq), (b, b_freq)| b_freq.cmp(a_freq).then(a.cmp(b)))
+        .map(|(category, _freq)| category)
+        .collect::<Vec<String>>();
+    let colored_ordered_categories = chart::colored(&ordered_categories);
+
+    let excluded = excluded.into_iter().sorted().dedup().collect::<Vec<_>>();
+
+    print_movers(&periods, args.top);
+
+    draw(
+        &periods,
+        &colored_ordered_categories,
+        &excluded,
+        &args.output,
+    )
+}
+
+// absolute change and change in percent (unless there is nothing to compare against)
+fn delta(base: f32, value: f32) -> (f32, Option<f32>) {
+    let abs = value - base;
+    let pct = (base != 0.0).then(|| abs / base * 100.0);
+
+    (abs, pct)
+}
+
+fn format_delta((abs, pct): (f32, Option<f32>)) -> String {
+    match pct {
+        Some(pct) => format!("{abs:+.0} ({pct:+.0}%)"),
+        None => format!("{abs:+.0} (new)"),
+    }
+}
+
+fn print_movers(periods: &[Period], top: usize) {
+    // unwrap: clap ensures there are at least two periods
+    let (base, last) = (periods.first().unwrap(), periods.last().unwrap());
+
+    let movers = base
+        .totals
+        .keys()
+        .chain(last.totals.keys())
+        .unique()
+        .map(|category| {
+            let base = base.totals.get(category).copied().unwrap_or_default();
+            let value = last.totals.get(category).copied().unwrap_or_default();
+            (category, delta(base, value))
+        })
+        .sorted_by(|(a, (a_abs, _)), (b, (b_abs, _))| {
+            b_abs
+                .abs()
+                .partial_cmp(&a_abs.abs())
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then(a.cmp(b))
+        })
+        .take(top)
+        .collect::<Vec<_>>();
+
+    let width = movers
+        .iter()
+        .map(|(category, _)| category.len())
+        .max()
+        .unwrap_or_default();
+
+    println!("biggest movers ({} vs {}):", last.label, base.label);
+    for (category, (abs, pct)) in movers {
+        let pct = match pct {
+            Some(pct) => format!("{pct:+.1}%"),
+            None => "new".to_string(),
+        };
+        println!("  {category:<width$}  {abs:>+10.2}  {pct:>8}");
+    }
+}
+
+fn draw(
+    periods: &[Period],
+    colored_ordered_categories: &[(String, RGBColor)],
+    excluded: &[String],
+    output: impl AsRef<Path>,
+) -> anyhow::Result<()> {
+    let canvas = BitMapBackend::new(&output, (640, 480)).into_drawing_area();
+    canvas.fill(&WHITE)?;
+
+    let canvas = canvas.margin(10, 10, 10, 10);
+
+    // each category gets `SLOT` units of the x axis to split between its bars
+    let ncategories = colored_ordered_categories.len() as u32;
+    let x_range = (0..ncategories * SLOT)
+        .with_key_points((0..ncategories).map(|i| i * SLOT + SLOT / 2).collect());
+    let y_range = {
+        let max = periods
+            .iter()
+            .flat_map(|period| period.totals.values())
+            .copied()
+            .max_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+            .unwrap_or_default();
+
+        // leave some room for the delta labels
+        0f32..(max * 1.1)
+    };
+
+    let mut chart = ChartBuilder::on(&canvas)
+        .caption("compare", ("sans-serif", 40).into_font())
+        .x_label_area_size(20)
+        .y_label_area_size(40)
+        .build_cartesian_2d(x_range, y_range)?;
+
+    let category_label = |x: &u32| {
+        colored_ordered_categories
+            .get((x / SLOT) as usize)
+            .map(|(category, _color)| category.clone())
+            .unwrap_or_default()
+    };
+
+    chart
+        .configure_mesh()
+        .disable_x_mesh()
+        .bold_line_style(WHITE.mix(0.3))
+        .x_label_formatter(&category_label)
+        .draw()?;
+
+    // unwrap: clap ensures there is at least one period
+    let base = periods.first().unwrap();
+
+    let width = SLOT * 8 / 10 / periods.len() as u32;
+    for (i, period) in periods.iter().enumerate() {
+        // the older the period the paler its bars
+        let alpha = 0.3 + 0.7 * (i + 1) as f64 / periods.len() as f64;
+
+        let mut series = vec![];
+        let mut labels = vec![];
+        for (j, (category, color)) in colored_ordered_categories.iter().enumerate() {
+            let value = period.totals.get(category).copied().unwrap_or_default();
+            let x = j as u32 * SLOT + SLOT / 10 + i as u32 * width;
+
+            series.push(Rectangle::new(
+                [(x, 0.0), (x + width, value)],
+                color.mix(alpha).filled(),
+            ));
+
+            if i > 0 {
+                let base = base.totals.get(category).copied().unwrap_or_default();
+                labels.push(Text::new(
+                    format_delta(delta(base, value)),
+                    (x + width / 2, value),
+                    ("sans-serif", 11)
+                        .into_font()
+                        .color(&BLACK)
+                        .pos(Pos::new(HPos::Center, VPos::Bottom)),
+                ));
+            }
+        }
+
+        let style = BLACK.mix(alpha).filled();
+        chart
+            .draw_series(series)?
+            .legend(move |(x, y)| Rectangle::new([(x - 4, y - 4), (x + 4, y + 4)], style))
+            .label(&period.label);
+        chart.draw_series(labels)?;
+    }
+
+    chart
+        .configure_series_labels()
+        .position(SeriesLabelPosition::UpperRight)
+        .margin(20)
+        .legend_area_size(5)
+        .border_style(BLUE)
+        .background_style(BLUE.mix(0.1))
+        .label_font(("Calibri", 20))
+        .draw()?;
+
+    draw_filter_note(&canvas, excluded)?;
+
+    canvas.present()?;
+
+    Ok(())
+}
@@ -0,0 +1,260 @@
+use anyhow::{bail, Context};
+use chrono::NaiveDate;
+use itertools::Itertools;
+
+use std::{
+    collections::HashMap,
+    fmt::Write as _,
+    io::{BufRead, BufReader},
+    path::PathBuf,
+};
+
+use crate::{calculate, filter::Filter, infer_month, month_file::MonthFile};
+
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    #[clap(short, long)]
+    format: Format,
+    /// file with `<category> <account>` lines. unlisted categories go to `Expenses:<category>`
+    #[clap(short, long)]
+    accounts: Option<PathBuf>,
+    /// the account every expense is paid from
+    #[clap(long, default_value = "Assets:Cash")]
+    funding_account: String,
+    /// commodity of the amounts (required by beancount)
+    #[clap(long)]
+    commodity: Option<String>,
+    /// journal file (printed when omitted)
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+    #[command(flatten)]
+    filter: Filter,
+    /// month files named like `jul-2023`
+    #[clap(required = true)]
+    data_files: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Format {
+    Ledger,
+    Hledger,
+    Beancount,
+}
+
+struct Day<'a> {
+    date: NaiveDate,
+    // (category, amount)
+    postings: Vec<(&'a str, f32)>,
+    comments: Vec<&'a str>,
+}
+
+pub fn run(args: Args) -> anyhow::Result<()> {
+    let commodity = match (args.format, &args.commodity) {
+        (Format::Beancount, None) => bail!("beancount needs a --commodity"),
+        (_, commodity) => commodity.as_deref(),
+    };
+
+    let accounts = match &args.accounts {
+        Some(path) => load_accounts(path).context("failed to load accounts")?,
+        None => HashMap::new(),
+    };
+
+    let mut files = vec![];
+    for data_file in &args.data_files {
+        let (year, month) = infer_month(data_file)?;
+        let file = MonthFile::load(data_file)?;
+        let (mut stats, _ordered_categories) = calculate(&file.text(), (year, month))
+            .context(format!("failed to calculate ({})", data_file.display()))?;
+        args.filter
+            .apply(&mut stats)
+            .context("failed to filter categories")?;
+
+        files.push(((year, month), file, stats));
+    }
+
+    let mut days = vec![];
+    for ((year, month), file, stats) in &files {
+        let mut comments = file.day_comments();
+
+        for (day, day_stats) in stats {
+            let postings = day_stats
+                .iter()
+                .sorted_by(|(a, _), (b, _)| a.cmp(b))
+                .flat_map(|(category, values)| {
+                    values.iter().map(|value| (category.as_str(), *value))
+                })
+                .collect::<Vec<_>>();
+
+            if postings.is_empty() {
+                continue;
+            }
+
+            days.push(Day {
+                date: NaiveDate::from_ymd_opt(*year, *month, *day)
+                    .context(format!("no such day: {day}"))?,
+                postings,
+                comments: comments.remove(day).unwrap_or_default(),
+            });
+        }
+    }
+    days.sort_by_key(|day| day.date);
+
+    let account = |category: &str| match accounts.get(category) {
+        Some(account) => account.clone(),
+        None => format!("Expenses:{category}"),
+    };
+
+    let journal = match args.format {
+        Format::Ledger | Format::Hledger => {
+            ledger(&days, &account, &args.funding_account, commodity)
+        }
+        // unwrap: checked above
+        Format::Beancount => beancount(&days, &account, &args.funding_account, commodity.unwrap()),
+    };
+
+    match &args.output {
+        Some(output) => fs_err::write(output, journal).context("failed to write journal")?,
+        None => print!("{journal}"),
+    }
+
+    Ok(())
+}
+
+pub fn load_accounts(path: &PathBuf) -> anyhow::Result<HashMap<String, String>> {
+    let data = fs_err::File::open(path)
+        .map(BufReader::new)
+        .context("failed to open accounts file")?;
+
+    let mut accounts = HashMap::new();
+    for (n, line) in data.lines().enumerate() {
+        let line = line.context("failed to read line")?;
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (category, account) = line
+            .split_once(char::is_whitespace)
+            .context(format!("failed to parse account (line: {})", n + 1))?;
+        accounts.insert(category.to_string(), account.trim().to_string());
+    }
+
+    Ok(accounts)
+}
+
+fn amount(value: f32, commodity: Option<&str>) -> String {
+    match commodity {
+        Some(commodity) => format!("{value:.2} {commodity}"),
+        None => format!("{value:.2}"),
+    }
+}
+
+// ledger and hledger read the same syntax. comments become transaction
+// comments so that hledger style `tag:value` pairs in them act as tags
+fn ledger(
+    days: &[Day],
+    account: &dyn Fn(&str) -> String,
+    funding_account: &str,
+    commodity: Option<&str>,
+) -> String {
+    let mut journal = String::new();
+
+    for day in days {
+        // unwrap: writing into a `String` never fails
+        writeln!(journal, "{} expenses", day.date).unwrap();
+        for comment in &day.comments {
+            writeln!(journal, "    ; {comment}").unwrap();
+        }
+        for (category, value) in &day.postings {
+            writeln!(
+                journal,
+                "    {}  {}",
+                account(category),
+                amount(*value, commodity)
+            )
+            .unwrap();
+        }
+        writeln!(journal, "    {funding_account}").unwrap();
+        writeln!(journal).unwrap();
+    }
+
+    journal
+}
+
+// beancount wants capitalized account components, `open` directives for
+// every account and metadata instead of tags (`tag:value` pairs are converted)
+fn beancount(
+    days: &[Day],
+    account: &dyn Fn(&str) -> String,
+    funding_account: &str,
+    commodity: &str,
+) -> String {
+    let capitalize = |account: String| {
+        account
+            .split(':')
+            .map(|component| {
+                let mut chars = component.chars();
+                match chars.next() {
+                    Some(first) => first.to_uppercase().chain(chars).collect::<String>(),
+                    None => String::new(),
+                }
+            })
+            .join(":")
+    };
+
+    let mut journal = String::new();
+
+    if let Some(first) = days.first() {
+        let accounts = days
+            .iter()
+            .flat_map(|day| day.postings.iter())
+            .map(|(category, _value)| capitalize(account(category)))
+            .chain([capitalize(funding_account.to_string())])
+            .sorted()
+            .dedup();
+
+        for account in accounts {
+            // unwrap: writing into a `String` never fails
+            writeln!(journal, "{} open {account}", first.date).unwrap();
+        }
+        writeln!(journal).unwrap();
+    }
+
+    for day in days {
+        writeln!(journal, "{} * \"expenses\"", day.date).unwrap();
+        // metadata has to come first and its keys must be unique
+        let tags = day
+            .comments
+            .iter()
+            .flat_map(|comment| tags(comment))
+            .unique_by(|(key, _value)| *key);
+        for (key, value) in tags {
+            writeln!(journal, "  {key}: \"{}\"", value.replace('"', "'")).unwrap();
+        }
+        for comment in &day.comments {
+            writeln!(journal, "  ; {comment}").unwrap();
+        }
+        for (category, value) in &day.postings {
+            let account = capitalize(account(category));
+            writeln!(journal, "  {account}  {}", amount(*value, Some(commodity))).unwrap();
+        }
+        writeln!(journal, "  {}", capitalize(funding_account.to_string())).unwrap();
+        writeln!(journal).unwrap();
+    }
+
+    journal
+}
+
+// hledger style `tag:value` words of a comment
+fn tags(comment: &str) -> impl Iterator<Item = (&str, &str)> {
+    comment.split_whitespace().filter_map(|word| {
+        let (key, value) = word.split_once(':')?;
+        let valid = key.starts_with(|c: char| c.is_ascii_lowercase())
+            && key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+        valid.then_some((key, value.trim_end_matches(',')))
+    })
+}
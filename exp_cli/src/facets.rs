@@ -0,0 +1,133 @@
+use plotters::prelude::*;
+
+use std::path::Path;
+
+use crate::{chart, day_label, day_span, days_so_far, draw_filter_note, weekends, Stats};
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum Scale {
+    Shared,
+    Independent,
+}
+
+pub fn draw(
+    (year, month): (i32, u32),
+    stats: &Stats,
+    ordered_categories: Vec<String>,
+    scale: Scale,
+    excluded: &[String],
+    output: impl AsRef<Path>,
+) -> anyhow::Result<()> {
+    // categories which have been filtered out do not get a panel
+    let colored_ordered_categories = chart::colored(&ordered_categories)
+        .into_iter()
+        .filter(|(category, _color)| {
+            stats
+                .values()
+                .any(|day_stats| day_stats.contains_key(category))
+        })
+        .collect::<Vec<(String, RGBColor)>>();
+
+    // daily amounts of every category
+    let panels = colored_ordered_categories
+        .iter()
+        .map(|(category, _color)| {
+            stats
+                .iter()
+                .map(|(day, day_stats)| {
+                    let value = day_stats
+                        .get(category)
+                        .map(|values| values.iter().sum::<f32>())
+                        .unwrap_or_default();
+                    (*day, value)
+                })
+                .collect::<Vec<(u32, f32)>>()
+        })
+        .collect::<Vec<_>>();
+
+    let max = |values: &[(u32, f32)]| {
+        values
+            .iter()
+            .map(|(_day, value)| *value)
+            .max_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+            .unwrap_or_default()
+    };
+    let shared_max = panels
+        .iter()
+        .map(|values| max(values))
+        .max_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+        .unwrap_or_default();
+
+    let canvas = BitMapBackend::new(&output, (960, 720)).into_drawing_area();
+    canvas.fill(&WHITE)?;
+
+    let canvas = canvas.margin(10, 10, 10, 10);
+
+    let cols = (panels.len() as f64).sqrt().ceil().max(1.0) as usize;
+    let rows = panels.len().div_ceil(cols).max(1);
+    let areas = canvas.split_evenly((rows, cols));
+
+    let days = stats.len() as u32;
+    let mean_days = days_so_far((year, month), stats);
+
+    for (((category, color), values), area) in colored_ordered_categories
+        .iter()
+        .zip(panels.iter())
+        .zip(areas.iter())
+    {
+        let max = match scale {
+            Scale::Shared => shared_max,
+            Scale::Independent => max(values),
+        };
+
+        // one label a week is all the room there is
+        let x_range = (0..days * 2).with_key_points(
+            (1..=days)
+                .step_by(7)
+                .map(|day| day * 2 - 1)
+                .collect::<Vec<_>>(),
+        );
+
+        let mut chart = ChartBuilder::on(area)
+            .margin(5)
+            .caption(category, ("sans-serif", 16).into_font())
+            .x_label_area_size(15)
+            .y_label_area_size(35)
+            .build_cartesian_2d(x_range, 0f32..max)?;
+
+        chart
+            .configure_mesh()
+            .disable_x_mesh()
+            .bold_line_style(WHITE.mix(0.3))
+            .x_label_formatter(&day_label)
+            .label_style(("sans-serif", 10))
+            .draw()?;
+
+        chart.draw_series(weekends((year, month), days, max))?;
+
+        chart.draw_series(values.iter().filter(|(_day, value)| *value > 0.0).map(
+            |(day, value)| {
+                let (x0, x1) = day_span(*day);
+                Rectangle::new([(x0, 0.0), (x1, *value)], color.filled())
+            },
+        ))?;
+
+        let total = values
+            .iter()
+            .filter(|(day, _value)| *day <= mean_days)
+            .map(|(_day, value)| value)
+            .sum::<f32>();
+        let mean = total / mean_days as f32;
+
+        chart.draw_series(LineSeries::new(
+            [(0, mean), (days * 2, mean)],
+            BLACK.mix(0.6).stroke_width(1),
+        ))?;
+    }
+
+    draw_filter_note(&canvas, excluded)?;
+
+    canvas.present()?;
+
+    Ok(())
+}
@@ -0,0 +1,58 @@
+use anyhow::Context;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+
+use std::collections::BTreeSet;
+
+use crate::Stats;
+
+#[derive(Debug, Default, clap::Args)]
+pub struct Filter {
+    /// only keep categories matching this glob (can be repeated)
+    #[clap(long)]
+    pub only: Vec<String>,
+    /// drop categories matching this glob (can be repeated)
+    #[clap(long)]
+    pub exclude: Vec<String>,
+}
+
+impl Filter {
+    // removes filtered out categories and returns their names
+    pub fn apply(&self, stats: &mut Stats) -> anyhow::Result<Vec<String>> {
+        let only = build(&self.only).context("failed to build --only filter")?;
+        let exclude = build(&self.exclude).context("failed to build --exclude filter")?;
+
+        let keep = |category: &str| {
+            (self.only.is_empty() || only.is_match(category)) && !exclude.is_match(category)
+        };
+
+        let mut excluded = BTreeSet::new();
+        for day_stats in stats.values_mut() {
+            day_stats.retain(|category, _values| {
+                keep(category) || {
+                    excluded.insert(category.clone());
+                    false
+                }
+            });
+        }
+
+        Ok(excluded.into_iter().collect())
+    }
+}
+
+fn build(patterns: &[String]) -> anyhow::Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern).context(format!("invalid glob: {pattern}"))?);
+    }
+
+    builder.build().context("failed to build glob set")
+}
+
+// a short human readable summary of what is not on the chart
+pub fn note(excluded: &[String]) -> Option<String> {
+    match excluded.len() {
+        0 => None,
+        1..=3 => Some(format!("without {}", excluded.join(", "))),
+        n => Some(format!("without {n} categories")),
+    }
+}
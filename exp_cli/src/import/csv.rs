@@ -0,0 +1,147 @@
+use anyhow::{bail, Context};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+
+use std::path::PathBuf;
+
+use super::{parse_amount, Common, Fingerprints, Transaction};
+
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    #[command(flatten)]
+    pub common: Common,
+    /// column with the transaction date (zero based)
+    #[clap(long, default_value = "0")]
+    date_column: usize,
+    /// column with the amount (zero based)
+    #[clap(long, default_value = "1")]
+    amount_column: usize,
+    /// column with the description (zero based)
+    #[clap(long, default_value = "2")]
+    description_column: usize,
+    /// chrono format of the date column
+    #[clap(long, default_value = "%Y-%m-%d")]
+    date_format: String,
+    #[clap(long, default_value = ",")]
+    delimiter: char,
+    /// the first line is data rather than column names
+    #[clap(long)]
+    no_header: bool,
+    /// expenses are positive amounts in the statement (they are negative by default)
+    #[clap(long)]
+    expenses_positive: bool,
+    /// the statement exported by the bank
+    statement: PathBuf,
+}
+
+pub fn read(args: &Args) -> anyhow::Result<Vec<Transaction>> {
+    let data = fs_err::read_to_string(&args.statement)?;
+
+    let mut transactions = vec![];
+    let mut fingerprints = Fingerprints::default();
+    for (n, line) in data.lines().enumerate().skip(usize::from(!args.no_header)) {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let fields = split(line, args.delimiter).context(format!("line: {}", n + 1))?;
+        let field = |column: usize| {
+            fields
+                .get(column)
+                .map(|field| field.trim())
+                .context(format!("no column {column} (line: {})", n + 1))
+        };
+
+        let date = NaiveDate::parse_from_str(field(args.date_column)?, &args.date_format)
+            .context(format!("failed to parse date (line: {})", n + 1))?;
+        let amount = parse_amount(field(args.amount_column)?)
+            .context(format!("failed to parse amount (line: {})", n + 1))?;
+        let description = field(args.description_column)?.to_string();
+
+        // incoming money is not an expense
+        let amount = if args.expenses_positive {
+            amount
+        } else {
+            -amount
+        };
+        if amount <= Decimal::ZERO {
+            continue;
+        }
+
+        transactions.push(Transaction {
+            // statements have no ids of their own so the whole row is the identity
+            id: fingerprints.next(line),
+            date,
+            amount,
+            description,
+        });
+    }
+
+    Ok(transactions)
+}
+
+// splits a line honoring double quoted fields (`""` is an escaped quote)
+fn split(line: &str, delimiter: char) -> anyhow::Result<Vec<String>> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' => quoted = !quoted,
+            c if c == delimiter && !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+
+    if quoted {
+        bail!("unterminated quote");
+    }
+    fields.push(field);
+
+    Ok(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identical_rows() {
+        let statement = std::env::temp_dir().join(format!("exp-csv-{}", std::process::id()));
+        fs_err::write(
+            &statement,
+            "date,amount,description\n\
+             2023-07-14,-3.50,coffee\n\
+             2023-07-14,-3.50,coffee\n\
+             2023-07-15,\"-1,200\",rent\n",
+        )
+        .unwrap();
+
+        let args = Args {
+            common: Common {
+                rules: PathBuf::new(),
+                dir: PathBuf::new(),
+            },
+            date_column: 0,
+            amount_column: 1,
+            description_column: 2,
+            date_format: "%Y-%m-%d".to_string(),
+            delimiter: ',',
+            no_header: false,
+            expenses_positive: false,
+            statement: statement.clone(),
+        };
+        let transactions = read(&args).unwrap();
+        fs_err::remove_file(&statement).unwrap();
+
+        assert_eq!(transactions.len(), 3);
+        assert_ne!(transactions[0].id, transactions[1].id);
+        assert_eq!(transactions[2].amount, Decimal::new(1200, 0));
+    }
+}
@@ -0,0 +1,422 @@
+use anyhow::{bail, Context};
+use chrono::{Datelike, NaiveDate};
+use rust_decimal::Decimal;
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::PathBuf,
+};
+
+use crate::{export::load_accounts, month_file::MonthFile, month_file_path};
+
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    /// file with `<category> <account>` lines (the same one `export` takes).
+    /// unlisted `Expenses:<category>` accounts become `<category>`
+    #[clap(short, long)]
+    accounts: Option<PathBuf>,
+    /// directory to write month files to
+    #[clap(short, long, default_value = ".")]
+    dir: PathBuf,
+    /// replace month files which already exist
+    #[clap(long)]
+    force: bool,
+    /// ledger, hledger or beancount journal
+    journal: PathBuf,
+}
+
+struct Posting {
+    account: String,
+    // `None` when elided (balances the transaction)
+    amount: Option<Decimal>,
+}
+
+struct Entry {
+    line: usize,
+    date: NaiveDate,
+    postings: Vec<Posting>,
+    comments: Vec<String>,
+}
+
+// directives which carry nothing exp could lose by skipping them
+const HARMLESS: &[&str] = &[
+    "account",
+    "commodity",
+    "payee",
+    "tag",
+    "option",
+    "decimal-mark",
+    "open",
+    "close",
+];
+
+pub fn run(args: Args) -> anyhow::Result<()> {
+    let text = fs_err::read_to_string(&args.journal)?;
+    let entries = parse(&text).context("failed to parse journal")?;
+
+    let categories = match &args.accounts {
+        Some(path) => load_accounts(path)
+            .context("failed to load accounts")?
+            .into_iter()
+            .map(|(category, account)| (account, category))
+            .collect(),
+        None => HashMap::new(),
+    };
+    let category = |account: &str| -> Option<String> {
+        if let Some(category) = categories.get(account) {
+            return Some(category.clone());
+        }
+
+        let (prefix, category) = account.split_once(':')?;
+        // categories are single words in month files
+        prefix
+            .eq_ignore_ascii_case("expenses")
+            .then(|| category.to_lowercase().replace(' ', "-"))
+    };
+
+    // collected first so that refunds can be taken off the expenses of their day
+    let mut amounts = BTreeMap::<(NaiveDate, String), Vec<Decimal>>::new();
+    let mut comments = BTreeMap::<NaiveDate, Vec<String>>::new();
+
+    for entry in entries {
+        let elided = entry
+            .postings
+            .iter()
+            .filter(|posting| posting.amount.is_none())
+            .count();
+        if elided > 1 {
+            bail!(
+                "more than one posting without an amount (line: {})",
+                entry.line
+            );
+        }
+        let balance = -entry
+            .postings
+            .iter()
+            .filter_map(|posting| posting.amount)
+            .sum::<Decimal>();
+
+        let mut any = false;
+        for posting in &entry.postings {
+            let Some(category) = category(&posting.account) else {
+                continue;
+            };
+
+            let amount = posting.amount.unwrap_or(balance);
+            if !amount.is_zero() {
+                amounts
+                    .entry((entry.date, category))
+                    .or_default()
+                    .push(amount);
+                any = true;
+            }
+        }
+
+        if any {
+            // a tag which is also a word of another comment comes from beancount metadata
+            // exported along with the comment it was taken from
+            let entry_comments = entry.comments.iter().filter(|comment| {
+                !entry.comments.iter().any(|other| {
+                    other != *comment && other.split_whitespace().any(|word| word == *comment)
+                })
+            });
+            comments
+                .entry(entry.date)
+                .or_default()
+                .extend(entry_comments.cloned());
+        }
+    }
+
+    let mut files = BTreeMap::<(i32, u32), MonthFile>::new();
+    let mut days = HashSet::new();
+    let mut postings = 0;
+
+    for ((date, category), amounts) in amounts {
+        let (amounts, left) = net(&amounts);
+        if !left.is_zero() {
+            eprintln!(
+                "warning: refunds of {left} exceed the expenses of {category} on {date}, the rest is ignored"
+            );
+        }
+        if amounts.is_empty() {
+            continue;
+        }
+
+        files
+            .entry((date.year(), date.month()))
+            .or_default()
+            .add(date.day(), &category, &amounts);
+        days.insert(date);
+        postings += amounts.len();
+    }
+
+    for (date, comments) in comments {
+        // the day is not there when all of its expenses were refunded
+        if !days.contains(&date) {
+            continue;
+        }
+        // unwrap: the file has been created along with the day
+        let file = files.get_mut(&(date.year(), date.month())).unwrap();
+        for comment in comments {
+            file.add_comment(date.day(), &comment)?;
+        }
+    }
+
+    let files = files
+        .into_iter()
+        .map(|((year, month), file)| {
+            let path = month_file_path(&args.dir, (year, month));
+            file.validate((year, month))
+                .context(format!("refusing to write invalid {}", path.display()))?;
+
+            Ok((path, file))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    // checked before writing anything so that a failure leaves no partial import
+    if !args.force {
+        for (path, _file) in &files {
+            if path.exists() {
+                bail!(
+                    "{} already exists (use --force to replace it)",
+                    path.display()
+                );
+            }
+        }
+    }
+
+    for (path, file) in &files {
+        file.save(path)
+            .context(format!("failed to save {}", path.display()))?;
+        println!("wrote {}", path.display());
+    }
+    println!("imported {postings} postings");
+
+    Ok(())
+}
+
+fn parse(text: &str) -> anyhow::Result<Vec<Entry>> {
+    let mut entries = vec![];
+    let mut current = None::<Entry>;
+
+    for (n, raw) in text.lines().enumerate() {
+        let n = n + 1;
+        let line = raw.trim();
+
+        if line.is_empty() {
+            entries.extend(current.take());
+            continue;
+        }
+
+        if raw.starts_with([' ', '\t']) {
+            // indented lines of skipped directives end up here as well
+            if let Some(entry) = current.as_mut() {
+                parse_indented(entry, line).context(format!("line: {n}"))?;
+            }
+            continue;
+        }
+
+        entries.extend(current.take());
+
+        if line.starts_with([';', '#', '*', '%', '|']) {
+            continue;
+        }
+
+        let (word, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+
+        if !word.starts_with(|c: char| c.is_ascii_digit()) {
+            if !HARMLESS.contains(&word) {
+                warn(n, &format!("unsupported directive `{word}`"));
+            }
+            continue;
+        }
+
+        // `2023-07-01`, `2023/07/01` or `2023.07.01` with an optional `=<secondary date>`
+        let date = word
+            .split('=')
+            .next()
+            .unwrap_or_default()
+            .replace(['/', '.'], "-");
+        let Ok(date) = NaiveDate::parse_from_str(&date, "%Y-%m-%d") else {
+            warn(n, &format!("unsupported date `{word}`"));
+            continue;
+        };
+
+        // beancount puts a keyword after the date of its directives
+        let (keyword, _rest) = rest
+            .trim()
+            .split_once(char::is_whitespace)
+            .unwrap_or((rest.trim(), ""));
+        match keyword {
+            "*" | "!" | "txn" => {}
+            keyword if HARMLESS.contains(&keyword) => continue,
+            "balance" | "pad" | "price" | "note" | "document" | "event" | "custom" | "query" => {
+                warn(n, &format!("unsupported directive `{keyword}`"));
+                continue;
+            }
+            _ => {}
+        }
+
+        let mut entry = Entry {
+            line: n,
+            date,
+            postings: vec![],
+            comments: vec![],
+        };
+        if let Some((_description, comment)) = rest.split_once(';') {
+            push_comment(&mut entry, comment);
+        }
+
+        current = Some(entry);
+    }
+    entries.extend(current);
+
+    Ok(entries)
+}
+
+fn parse_indented(entry: &mut Entry, line: &str) -> anyhow::Result<()> {
+    if let Some(comment) = line.strip_prefix([';', '#']) {
+        push_comment(entry, comment);
+        return Ok(());
+    }
+
+    // beancount metadata (`key: "value"`) is kept as a hledger style tag
+    if let Some((key, value)) = line.split_once(": ") {
+        if !key.contains([':', ' ']) {
+            push_comment(entry, &format!("{key}:{}", value.trim().trim_matches('"')));
+            return Ok(());
+        }
+    }
+
+    let (posting, comment) = line.split_once(';').unwrap_or((line, ""));
+    push_comment(entry, comment);
+
+    // hledger accounts may contain single spaces so two of them (or a tab) separate the amount
+    let posting = posting.trim().trim_start_matches(['*', '!']).trim_start();
+    let (account, amount) = match posting.split_once("  ").or(posting.split_once('\t')) {
+        Some(split) => split,
+        None => match posting.split_once(' ') {
+            Some((account, amount)) if amount.contains(|c: char| c.is_ascii_digit()) => {
+                (account, amount)
+            }
+            _ => (posting, ""),
+        },
+    };
+
+    // costs (`@`) and balance assertions (`=`) do not change the amount
+    let amount = amount.split(['@', '=']).next().unwrap_or_default().trim();
+    let amount = if amount.is_empty() {
+        None
+    } else {
+        Some(parse_amount(amount).context(format!("failed to parse amount: {amount}"))?)
+    };
+
+    entry.postings.push(Posting {
+        // virtual postings are treated like regular ones
+        account: account
+            .trim()
+            .trim_matches(['(', ')', '[', ']'])
+            .to_string(),
+        amount,
+    });
+
+    Ok(())
+}
+
+// a number with its commodity before or after it (`€1,200.50`, `-12 EUR`, `"ACME 2" 3`).
+// commas group digits as ledger does by default, unless they come after the last
+// dot (`1.200,50` of european `commodity` directives)
+fn parse_amount(amount: &str) -> anyhow::Result<Decimal> {
+    // quoted commodities may contain anything, digits included
+    let unquoted = amount.split('"').step_by(2).collect::<String>();
+    let number = unquoted
+        .chars()
+        .filter(|c| c.is_ascii_digit() || matches!(c, '.' | ','))
+        .collect::<String>();
+    let number = match (number.rfind(','), number.rfind('.')) {
+        (Some(comma), Some(dot)) if comma > dot => number.replace('.', "").replace(',', "."),
+        _ => number.replace(',', ""),
+    };
+
+    let value = number.parse::<Decimal>()?;
+    Ok(if unquoted.contains('-') {
+        -value
+    } else {
+        value
+    })
+}
+
+// refunds (negative amounts) are taken off the other amounts of the same day and
+// category, the latest ones first. returns what is left of both
+fn net(amounts: &[Decimal]) -> (Vec<Decimal>, Decimal) {
+    let mut refund = -amounts
+        .iter()
+        .filter(|amount| amount.is_sign_negative())
+        .sum::<Decimal>();
+    let mut expenses = amounts
+        .iter()
+        .copied()
+        .filter(|amount| amount.is_sign_positive())
+        .collect::<Vec<_>>();
+
+    while refund > Decimal::ZERO {
+        let Some(last) = expenses.last_mut() else {
+            break;
+        };
+        if *last > refund {
+            *last -= refund;
+            refund = Decimal::ZERO;
+        } else {
+            refund -= *last;
+            expenses.pop();
+        }
+    }
+
+    (expenses, refund)
+}
+
+fn push_comment(entry: &mut Entry, comment: &str) {
+    let comment = comment.trim();
+    if !comment.is_empty() {
+        entry.comments.push(comment.to_string());
+    }
+}
+
+fn warn(line: usize, message: &str) {
+    eprintln!("warning: {message} is ignored (line: {line})");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn amounts() {
+        assert_eq!(parse_amount("€1,200").unwrap(), Decimal::new(1200, 0));
+        assert_eq!(parse_amount("$1,200.50").unwrap(), Decimal::new(120050, 2));
+        assert_eq!(parse_amount("-12 EUR").unwrap(), Decimal::new(-12, 0));
+        assert_eq!(parse_amount("€-12.5").unwrap(), Decimal::new(-125, 1));
+        assert_eq!(
+            parse_amount("1.200,50 EUR").unwrap(),
+            Decimal::new(120050, 2)
+        );
+        assert_eq!(parse_amount("\"ACME 2\" 3").unwrap(), Decimal::new(3, 0));
+    }
+
+    #[test]
+    fn refunds() {
+        let refund = parse_amount("-2.10").unwrap();
+        assert_eq!(refund, Decimal::new(-210, 2));
+
+        let (five, three) = (Decimal::new(5, 0), Decimal::new(3, 0));
+        assert_eq!(
+            net(&[five, three, refund]),
+            (vec![five, Decimal::new(90, 2)], Decimal::ZERO)
+        );
+        assert_eq!(
+            net(&[three, refund, refund]),
+            (vec![], Decimal::new(120, 2))
+        );
+        assert_eq!(net(&[refund]), (vec![], Decimal::new(210, 2)));
+    }
+}
@@ -0,0 +1,270 @@
+pub mod csv;
+pub mod journal;
+pub mod ofx;
+pub mod qif;
+
+use anyhow::Context;
+use chrono::{Datelike, NaiveDate};
+use regex::Regex;
+use rust_decimal::Decimal;
+
+use std::{
+    collections::{btree_map::Entry, BTreeMap, HashMap},
+    io::{BufRead, BufReader},
+    path::PathBuf,
+};
+
+use crate::{month_file::MonthFile, month_file_path};
+
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    #[command(subcommand)]
+    format: Format,
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum Format {
+    /// import a bank statement in CSV format
+    Csv(csv::Args),
+    /// import a bank statement in OFX format
+    Ofx(ofx::Args),
+    /// import a bank statement in QIF format
+    Qif(qif::Args),
+    /// convert the expenses of a ledger, hledger or beancount journal into month files
+    Journal(journal::Args),
+}
+
+// arguments shared by all the importers
+#[derive(Debug, clap::Args)]
+pub struct Common {
+    /// file with `<category> <regex>` lines. the first rule matching the description wins
+    #[clap(short, long)]
+    rules: PathBuf,
+    /// directory with month files (named like `jul-2023`)
+    #[clap(short, long, default_value = ".")]
+    dir: PathBuf,
+}
+
+pub struct Transaction {
+    // stable across re-imports of the same statement. the bank's
+    // own transaction id when the format has one
+    pub id: String,
+    pub date: NaiveDate,
+    pub amount: Decimal,
+    pub description: String,
+}
+
+pub fn run(args: Args) -> anyhow::Result<()> {
+    match args.format {
+        Format::Csv(args) => {
+            let transactions = csv::read(&args).context("failed to read csv statement")?;
+            import(&args.common, transactions)
+        }
+        Format::Ofx(args) => {
+            let transactions = ofx::read(&args).context("failed to read ofx statement")?;
+            import(&args.common, transactions)
+        }
+        Format::Qif(args) => {
+            let transactions = qif::read(&args).context("failed to read qif statement")?;
+            import(&args.common, transactions)
+        }
+        Format::Journal(args) => journal::run(args),
+    }
+}
+
+struct Rules(Vec<(String, Regex)>);
+
+impl Rules {
+    fn load(path: &PathBuf) -> anyhow::Result<Self> {
+        let data = fs_err::File::open(path)
+            .map(BufReader::new)
+            .context("failed to open rules file")?;
+
+        let mut rules = vec![];
+        for (n, line) in data.lines().enumerate() {
+            let line = line.context("failed to read line")?;
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (category, pattern) = line
+                .split_once(char::is_whitespace)
+                .context(format!("failed to parse rule (line: {})", n + 1))?;
+            let pattern = Regex::new(pattern.trim())
+                .context(format!("failed to parse rule regex (line: {})", n + 1))?;
+
+            rules.push((category.to_string(), pattern));
+        }
+
+        Ok(Self(rules))
+    }
+
+    fn categorize(&self, description: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(_category, pattern)| pattern.is_match(description))
+            .map(|(category, _pattern)| category.as_str())
+    }
+}
+
+// every imported transaction leaves a comment with its id next to the expenses of its day
+fn marker(transaction: &Transaction) -> String {
+    format!("import:{}", transaction.id)
+}
+
+fn import(common: &Common, transactions: Vec<Transaction>) -> anyhow::Result<()> {
+    let rules = Rules::load(&common.rules).context("failed to load rules")?;
+
+    let mut files = BTreeMap::<(i32, u32), (PathBuf, MonthFile, bool)>::new();
+    let mut unmatched = vec![];
+    let (mut imported, mut duplicates) = (0, 0);
+
+    for transaction in transactions {
+        let Some(category) = rules.categorize(&transaction.description) else {
+            unmatched.push(transaction);
+            continue;
+        };
+
+        let (year, month) = (transaction.date.year(), transaction.date.month());
+        let (_path, file, modified) = match files.entry((year, month)) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let path = month_file_path(&common.dir, (year, month));
+                let file =
+                    MonthFile::load(&path).context(format!("failed to load {}", path.display()))?;
+                entry.insert((path, file, false))
+            }
+        };
+
+        let marker = marker(&transaction);
+        if file
+            .comments()
+            .any(|comment| comment.split(' ').next() == Some(marker.as_str()))
+        {
+            duplicates += 1;
+            continue;
+        }
+
+        let day = transaction.date.day();
+        file.add(day, category, &[transaction.amount]);
+        file.add_comment(day, &format!("{marker} {}", transaction.description))?;
+        *modified = true;
+        imported += 1;
+    }
+
+    // everything is validated before anything is written
+    for ((year, month), (path, file, modified)) in files.iter() {
+        if *modified {
+            file.validate((*year, *month))
+                .context(format!("refusing to write invalid {}", path.display()))?;
+        }
+    }
+    for (path, file, modified) in files.values() {
+        if *modified {
+            file.save(path)
+                .context(format!("failed to save {}", path.display()))?;
+            println!("updated {}", path.display());
+        }
+    }
+
+    println!(
+        "imported: {imported}, already imported: {duplicates}, unmatched: {}",
+        unmatched.len()
+    );
+    for transaction in unmatched {
+        println!(
+            "  unmatched: {} {} {}",
+            transaction.date, transaction.amount, transaction.description
+        );
+    }
+
+    Ok(())
+}
+
+// banks tend to use thousands separators and decimal commas
+pub fn parse_amount(raw: &str) -> anyhow::Result<Decimal> {
+    let raw = raw.replace([' ', '\u{a0}'], "");
+    let raw = match (raw.rfind(','), raw.rfind('.')) {
+        (Some(comma), Some(dot)) if comma > dot => raw.replace('.', "").replace(',', "."),
+        (Some(_), Some(_)) => raw.replace(',', ""),
+        // a lone comma is a decimal one only when followed by cents (`12,5`, `12,50`),
+        // otherwise it separates thousands (`1,200`)
+        (Some(comma), None)
+            if raw.matches(',').count() == 1 && (2..=3).contains(&(raw.len() - comma)) =>
+        {
+            raw.replace(',', ".")
+        }
+        (Some(_), None) => raw.replace(',', ""),
+        _ => raw,
+    };
+
+    Ok(raw.parse()?)
+}
+
+// FNV-1a. unlike `DefaultHasher` it is guaranteed to stay the same between builds
+pub fn fingerprint(data: &str) -> String {
+    let hash = data.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+
+    format!("{hash:016x}")
+}
+
+// identical rows of a statement are still different transactions (two coffees
+// on the same day), so repeated rows get their occurrence appended to the fingerprint
+#[derive(Default)]
+pub struct Fingerprints(HashMap<String, usize>);
+
+impl Fingerprints {
+    pub fn next(&mut self, data: &str) -> String {
+        let fingerprint = fingerprint(data);
+        let occurrence = self.0.entry(fingerprint.clone()).or_default();
+        *occurrence += 1;
+
+        // the first one keeps the plain fingerprint so that older imports stay idempotent
+        match *occurrence {
+            1 => fingerprint,
+            n => format!("{fingerprint}-{n}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn amounts() {
+        assert_eq!(parse_amount("12.50").unwrap(), Decimal::new(1250, 2));
+        assert_eq!(parse_amount("-12,50").unwrap(), Decimal::new(-1250, 2));
+        assert_eq!(parse_amount("12,5").unwrap(), Decimal::new(125, 1));
+        assert_eq!(parse_amount("1,200").unwrap(), Decimal::new(1200, 0));
+        assert_eq!(
+            parse_amount("-1,200,300").unwrap(),
+            Decimal::new(-1200300, 0)
+        );
+        assert_eq!(parse_amount("1,200.50").unwrap(), Decimal::new(120050, 2));
+        assert_eq!(parse_amount("1.200,50").unwrap(), Decimal::new(120050, 2));
+        assert_eq!(parse_amount("1 200,50").unwrap(), Decimal::new(120050, 2));
+        // too many digits for an f32 to keep the cents
+        assert_eq!(
+            parse_amount("123456789.01").unwrap(),
+            Decimal::new(12345678901, 2)
+        );
+    }
+
+    #[test]
+    fn repeated_fingerprints() {
+        let mut fingerprints = Fingerprints::default();
+
+        let first = fingerprints.next("2023-07-14,-3.50,coffee");
+        let second = fingerprints.next("2023-07-14,-3.50,coffee");
+        let other = fingerprints.next("2023-07-14,-4.00,coffee");
+
+        assert_eq!(first, fingerprint("2023-07-14,-3.50,coffee"));
+        assert_eq!(second, format!("{first}-2"));
+        assert_eq!(other, fingerprint("2023-07-14,-4.00,coffee"));
+    }
+}
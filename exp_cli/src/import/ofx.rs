@@ -0,0 +1,108 @@
+use anyhow::Context;
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+
+use std::path::PathBuf;
+
+use super::{Common, Transaction};
+
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    #[command(flatten)]
+    pub common: Common,
+    /// the statement exported by the bank
+    statement: PathBuf,
+}
+
+// handles both the SGML flavour (1.x, closing tags are optional)
+// and the XML one (2.x) since only the leaf elements are looked at
+pub fn read(args: &Args) -> anyhow::Result<Vec<Transaction>> {
+    let data = fs_err::read_to_string(&args.statement)?;
+
+    let mut transactions = vec![];
+    let mut current = None::<Fields>;
+
+    for (tag, value) in elements(&data) {
+        match (tag.to_ascii_uppercase().as_str(), current.as_mut()) {
+            ("STMTTRN", _) => current = Some(Fields::default()),
+            ("/STMTTRN", Some(_)) => {
+                // unwrap: checked by the pattern
+                let fields = current.take().unwrap();
+                if let Some(transaction) = fields.transaction()? {
+                    transactions.push(transaction);
+                }
+            }
+            ("FITID", Some(fields)) => fields.id = Some(value),
+            ("DTPOSTED", Some(fields)) => fields.date = Some(value),
+            ("TRNAMT", Some(fields)) => fields.amount = Some(value),
+            ("NAME", Some(fields)) => fields.name = Some(value),
+            ("MEMO", Some(fields)) => fields.memo = Some(value),
+            _ => {}
+        }
+    }
+
+    Ok(transactions)
+}
+
+#[derive(Default)]
+struct Fields {
+    id: Option<String>,
+    date: Option<String>,
+    amount: Option<String>,
+    name: Option<String>,
+    memo: Option<String>,
+}
+
+impl Fields {
+    fn transaction(self) -> anyhow::Result<Option<Transaction>> {
+        let id = self.id.context("transaction without FITID")?;
+        let context = || format!("transaction {id}");
+
+        let date = self.date.context("no DTPOSTED").with_context(context)?;
+        // `20230701120000.000[-5:EST]`, only the date part matters
+        let date = date
+            .get(..8)
+            .and_then(|date| NaiveDate::parse_from_str(date, "%Y%m%d").ok())
+            .context(format!("failed to parse date: {date}"))
+            .with_context(context)?;
+
+        let amount = self.amount.context("no TRNAMT").with_context(context)?;
+        let amount = amount
+            .replace(',', ".")
+            .parse::<Decimal>()
+            .context(format!("failed to parse amount: {amount}"))
+            .with_context(context)?;
+
+        // incoming money is not an expense
+        if amount >= Decimal::ZERO {
+            return Ok(None);
+        }
+
+        let description = [self.name, self.memo]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        Ok(Some(Transaction {
+            id,
+            date,
+            amount: -amount,
+            description,
+        }))
+    }
+}
+
+// `<TAG>value` pairs in the order of appearance (the value is empty for aggregates)
+fn elements(data: &str) -> impl Iterator<Item = (&str, String)> {
+    data.split('<').skip(1).filter_map(|element| {
+        let (tag, value) = element.split_once('>')?;
+        let value = value
+            .trim()
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&amp;", "&");
+
+        Some((tag.trim(), value))
+    })
+}
@@ -0,0 +1,123 @@
+use anyhow::Context;
+use chrono::{Datelike, NaiveDate};
+use rust_decimal::Decimal;
+
+use std::path::PathBuf;
+
+use super::{Common, Fingerprints, Transaction};
+
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    #[command(flatten)]
+    pub common: Common,
+    /// chrono format of the dates (`1/2'23` style dates are read as `1/2/2023`)
+    #[clap(long, default_value = "%m/%d/%Y")]
+    date_format: String,
+    /// the statement exported by the bank
+    statement: PathBuf,
+}
+
+pub fn read(args: &Args) -> anyhow::Result<Vec<Transaction>> {
+    let data = fs_err::read_to_string(&args.statement)?;
+
+    let mut transactions = vec![];
+    let mut record = vec![];
+    let mut fingerprints = Fingerprints::default();
+
+    for (n, line) in data.lines().enumerate() {
+        let line = line.trim();
+
+        // `!Type:Bank` headers and blank lines carry nothing
+        if line.is_empty() || line.starts_with('!') {
+            continue;
+        }
+
+        if line != "^" {
+            record.push(line);
+            continue;
+        }
+
+        let transaction = parse(&record, &args.date_format, &mut fingerprints)
+            .context(format!("failed to parse record ending at line {}", n + 1))?;
+        transactions.extend(transaction);
+        record.clear();
+    }
+
+    Ok(transactions)
+}
+
+fn parse(
+    record: &[&str],
+    date_format: &str,
+    fingerprints: &mut Fingerprints,
+) -> anyhow::Result<Option<Transaction>> {
+    let field = |code: char| {
+        record
+            .iter()
+            .find_map(|line| line.strip_prefix(code))
+            .map(str::trim)
+    };
+
+    let raw_date = field('D').context("no date")?;
+    let date =
+        NaiveDate::parse_from_str(&raw_date.replace(' ', "").replace('\'', "/"), date_format)
+            .context(format!("failed to parse date: {raw_date}"))?;
+    // quicken writes the years of the 2000s as `'23`, which `%Y` reads as the year 23
+    let date = match date.year() {
+        year if raw_date.contains('\'') && year < 100 => date
+            .with_year(2000 + year)
+            .context(format!("failed to parse date: {raw_date}"))?,
+        _ => date,
+    };
+
+    let amount = field('T').or(field('U')).context("no amount")?;
+    let amount = amount
+        .replace(',', "")
+        .parse::<Decimal>()
+        .context(format!("failed to parse amount: {amount}"))?;
+
+    // incoming money is not an expense
+    if amount >= Decimal::ZERO {
+        return Ok(None);
+    }
+
+    let description = [field('P'), field('M')]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    // the check number is the only id QIF has and most transactions do not have one
+    let id = match field('N') {
+        Some(number) if !number.is_empty() => format!("{date}-{number}"),
+        _ => fingerprints.next(&record.join("\n")),
+    };
+
+    Ok(Some(Transaction {
+        id,
+        date,
+        amount: -amount,
+        description,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apostrophe_dates() {
+        let mut fingerprints = Fingerprints::default();
+        let record = ["D1/ 2'23", "T-3.50", "Pcoffee"];
+
+        let first = parse(&record, "%m/%d/%Y", &mut fingerprints)
+            .unwrap()
+            .unwrap();
+        let second = parse(&record, "%m/%d/%Y", &mut fingerprints)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(first.date, NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        assert_ne!(first.id, second.id);
+    }
+}
@@ -0,0 +1,455 @@
+use anyhow::{bail, Context};
+use itertools::Itertools;
+use serde_json::{json, Value};
+
+use std::{
+    collections::HashMap,
+    io::{BufRead, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{calculate, infer_month, month_file::MonthFile, totals};
+
+// the language server protocol over stdin/stdout, just the parts an editor needs
+// for month files: diagnostics, completion, hover and formatting
+pub fn run() -> anyhow::Result<()> {
+    let mut stdin = std::io::stdin().lock();
+    let mut stdout = std::io::stdout().lock();
+    let mut documents = HashMap::<String, String>::new();
+
+    while let Some(message) = read_message(&mut stdin)? {
+        // responses to requests of our own (there are none) have no method
+        let Some(method) = message["method"].as_str() else {
+            continue;
+        };
+        let params = &message["params"];
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+
+        let result = match method {
+            "initialize" => Some(json!({
+                "capabilities": {
+                    // the whole document on every change
+                    "textDocumentSync": 1,
+                    "completionProvider": {},
+                    "hoverProvider": true,
+                    "documentFormattingProvider": true,
+                },
+                "serverInfo": { "name": "exp_cli" },
+            })),
+            "shutdown" => Some(Value::Null),
+            "exit" => return Ok(()),
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                documents.insert(uri.to_string(), text.to_string());
+                publish_diagnostics(&mut stdout, uri, text)?;
+                None
+            }
+            "textDocument/didChange" => {
+                let changes = params["contentChanges"].as_array();
+                if let Some(text) = changes.and_then(|changes| changes.last()) {
+                    let text = text["text"].as_str().unwrap_or_default();
+                    documents.insert(uri.to_string(), text.to_string());
+                    publish_diagnostics(&mut stdout, uri, text)?;
+                }
+                None
+            }
+            "textDocument/didClose" => {
+                documents.remove(uri);
+                notify(
+                    &mut stdout,
+                    "textDocument/publishDiagnostics",
+                    json!({ "uri": uri, "diagnostics": [] }),
+                )?;
+                None
+            }
+            "textDocument/completion" => {
+                let text = documents.get(uri).map(String::as_str).unwrap_or_default();
+                Some(completion(uri, text, &params["position"]))
+            }
+            "textDocument/hover" => {
+                let text = documents.get(uri).map(String::as_str).unwrap_or_default();
+                Some(hover(uri, text, &params["position"]))
+            }
+            "textDocument/formatting" => {
+                let text = documents.get(uri).map(String::as_str).unwrap_or_default();
+                Some(formatting(uri, text))
+            }
+            _ => None,
+        };
+
+        // notifications have no id and get no response
+        let Some(id) = message.get("id") else {
+            continue;
+        };
+        let response = match result {
+            Some(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            None => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": -32601, "message": format!("unsupported method: {method}") },
+            }),
+        };
+        write_message(&mut stdout, &response)?;
+    }
+
+    Ok(())
+}
+
+// `None` once the client has closed stdin
+fn read_message(stdin: &mut impl BufRead) -> anyhow::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if stdin.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+
+        let header = header.trim();
+        if header.is_empty() {
+            break;
+        }
+
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = Some(
+                value
+                    .trim()
+                    .parse::<usize>()
+                    .context("invalid content length")?,
+            );
+        }
+    }
+
+    let Some(length) = length else {
+        bail!("message without content length");
+    };
+
+    let mut body = vec![0; length];
+    stdin.read_exact(&mut body)?;
+
+    Ok(Some(
+        serde_json::from_slice(&body).context("invalid message")?,
+    ))
+}
+
+fn write_message(stdout: &mut impl Write, message: &Value) -> anyhow::Result<()> {
+    let body = message.to_string();
+    write!(stdout, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    stdout.flush()?;
+
+    Ok(())
+}
+
+fn notify(stdout: &mut impl Write, method: &str, params: Value) -> anyhow::Result<()> {
+    write_message(
+        stdout,
+        &json!({ "jsonrpc": "2.0", "method": method, "params": params }),
+    )
+}
+
+fn uri_to_path(uri: &str) -> PathBuf {
+    let path = uri.strip_prefix("file://").unwrap_or(uri);
+
+    // percent decoding, which is all that is needed for file uris
+    let mut bytes = vec![];
+    let mut rest = path.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let decoded = (byte == b'%')
+            .then(|| tail.get(..2))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+
+        match decoded {
+            Some(decoded) => {
+                bytes.push(decoded);
+                rest = &tail[2..];
+            }
+            None => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+
+    PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+fn line_range(line: usize, text: &str) -> Value {
+    let length = text.lines().nth(line).map(|line| line.chars().count());
+
+    json!({
+        "start": { "line": line, "character": 0 },
+        "end": { "line": line, "character": length.unwrap_or_default() },
+    })
+}
+
+// the day of every line of a day section, the day line itself included
+fn line_days(text: &str) -> Vec<Option<u32>> {
+    let mut days = vec![];
+    let mut day = None;
+
+    for line in text.lines() {
+        let line = line.trim();
+
+        if line.is_empty() {
+            day = None;
+        } else if day.is_none() && !line.starts_with('#') {
+            day = line.parse().ok();
+        }
+
+        days.push(day);
+    }
+
+    days
+}
+
+// how often categories appeared in the months before this one (month files next to it)
+fn category_frequency(path: &Path, (year, month): (i32, u32)) -> HashMap<String, usize> {
+    let mut category_frequency = HashMap::new();
+
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let Ok(entries) = fs_err::read_dir(dir) else {
+        return category_frequency;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(other) = infer_month(&path) else {
+            continue;
+        };
+        if other >= (year, month) {
+            continue;
+        }
+
+        // broken files are of no help, and not this file's problem
+        let Ok(text) = fs_err::read_to_string(&path) else {
+            continue;
+        };
+        let Ok((stats, _ordered_categories)) = calculate(&text, other) else {
+            continue;
+        };
+
+        for day_stats in stats.values() {
+            for category in day_stats.keys() {
+                *category_frequency.entry(category.clone()).or_default() += 1;
+            }
+        }
+    }
+
+    category_frequency
+}
+
+fn publish_diagnostics(stdout: &mut impl Write, uri: &str, text: &str) -> anyhow::Result<()> {
+    let diagnostic = |line: usize, severity: u32, message: String| {
+        json!({
+            "range": line_range(line, text),
+            "severity": severity,
+            "source": "exp",
+            "message": message,
+        })
+    };
+
+    let path = uri_to_path(uri);
+    let diagnostics = match infer_month(&path) {
+        Err(err) => vec![diagnostic(0, 1, format!("{err:#}"))],
+        Ok((year, month)) => match calculate(text, (year, month)) {
+            // the parser puts the line into its errors
+            Err(err) => {
+                let message = format!("{err:#}");
+                let line = message
+                    .split("line: ")
+                    .nth(1)
+                    .and_then(|rest| rest.split(|c: char| !c.is_ascii_digit()).next())
+                    .and_then(|line| line.parse::<usize>().ok())
+                    .unwrap_or(1);
+
+                vec![diagnostic(line - 1, 1, message)]
+            }
+            Ok(_) => {
+                let known = category_frequency(&path, (year, month));
+                lint(text, &known)
+                    .into_iter()
+                    .map(|(line, message)| diagnostic(line, 2, message))
+                    .collect()
+            }
+        },
+    };
+
+    notify(
+        stdout,
+        "textDocument/publishDiagnostics",
+        json!({ "uri": uri, "diagnostics": diagnostics }),
+    )
+}
+
+// things the parser accepts but which are most likely mistakes. (line, message)
+fn lint(text: &str, known: &HashMap<String, usize>) -> Vec<(usize, String)> {
+    let mut warnings = vec![];
+    let mut last_day = 0;
+    let days = line_days(text);
+
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        let Some(day) = days[i] else {
+            continue;
+        };
+        if line.starts_with('#') {
+            continue;
+        }
+
+        // the first line of a section is the day itself
+        if i == 0 || days[i - 1] != Some(day) {
+            if day < last_day {
+                warnings.push((i, format!("day {day} comes after day {last_day}")));
+            }
+            last_day = day;
+            continue;
+        }
+
+        let mut tokens = line.split(' ');
+        let category = tokens.next().unwrap_or_default();
+        // nothing to compare against in the very first month
+        if !known.is_empty() && !known.contains_key(category) {
+            warnings.push((
+                i,
+                format!("category `{category}` does not appear in previous months"),
+            ));
+        }
+        if tokens.any(|value| value.parse::<f32>() == Ok(0.0)) {
+            warnings.push((i, "zero amount".to_string()));
+        }
+    }
+
+    warnings
+}
+
+fn completion(uri: &str, text: &str, position: &Value) -> Value {
+    let (cursor, character) = position_of(position);
+
+    let prefix = text
+        .lines()
+        .nth(cursor)
+        .map(|line| line.chars().take(character).collect::<String>())
+        .unwrap_or_default();
+    let in_section = cursor > 0 && line_days(text).get(cursor - 1).copied().flatten().is_some();
+
+    // only the first word of a data line is a category
+    if !in_section || prefix.contains(' ') || prefix.starts_with('#') {
+        return json!([]);
+    }
+
+    let path = uri_to_path(uri);
+    let mut frequency = match infer_month(&path) {
+        Ok((year, month)) => category_frequency(&path, (year, month)),
+        Err(_) => HashMap::new(),
+    };
+    // categories of this month count too, even if the file does not parse yet
+    for (i, line) in text.lines().enumerate() {
+        if i == cursor || line.trim().starts_with('#') {
+            continue;
+        }
+        if let Some(category) = line.split(' ').next().filter(|c| c.parse::<f32>().is_err()) {
+            if !category.is_empty() {
+                *frequency.entry(category.to_string()).or_default() += 1;
+            }
+        }
+    }
+
+    let items = frequency
+        .into_iter()
+        .sorted_by(|(a, a_freq), (b, b_freq)| b_freq.cmp(a_freq).then(a.cmp(b)))
+        .enumerate()
+        .map(|(i, (category, frequency))| {
+            json!({
+                "label": category,
+                // value
+                "kind": 12,
+                "detail": format!("used {frequency} times"),
+                "sortText": format!("{i:05}"),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    json!(items)
+}
+
+fn hover(uri: &str, text: &str, position: &Value) -> Value {
+    let (line, _character) = position_of(position);
+
+    let Ok((year, month)) = infer_month(&uri_to_path(uri)) else {
+        return Value::Null;
+    };
+    let Ok((stats, _ordered_categories)) = calculate(text, (year, month)) else {
+        return Value::Null;
+    };
+    let Some(day) = line_days(text).get(line).copied().flatten() else {
+        return Value::Null;
+    };
+    let Some(day_stats) = stats.get(&day) else {
+        return Value::Null;
+    };
+
+    let current = text.lines().nth(line).unwrap_or_default().trim();
+    let day_total = day_stats.values().flatten().sum::<f32>();
+
+    let contents = if current.parse::<u32>() == Ok(day) {
+        let categories = day_stats
+            .iter()
+            .sorted_by(|(a, _), (b, _)| a.cmp(b))
+            .map(|(category, values)| format!("- {category}: {:.2}", values.iter().sum::<f32>()))
+            .join("\n");
+
+        format!("**day {day}**: {day_total:.2}\n\n{categories}")
+    } else {
+        let category = current.split(' ').next().unwrap_or_default();
+        let Some(values) = day_stats.get(category) else {
+            return Value::Null;
+        };
+
+        let totals = totals(&stats);
+        let month_total = totals.values().sum::<f32>();
+        let category_total = totals.get(category).copied().unwrap_or_default();
+        let share = if month_total > 0.0 {
+            category_total / month_total * 100.0
+        } else {
+            0.0
+        };
+
+        format!(
+            "**{category}**\n\n- day {day}: {:.2}\n- month: {category_total:.2} ({share:.1}% of {month_total:.2})",
+            values.iter().sum::<f32>()
+        )
+    };
+
+    json!({ "contents": { "kind": "markdown", "value": contents } })
+}
+
+fn formatting(uri: &str, text: &str) -> Value {
+    // formatting a file that does not parse could make things worse
+    let Ok((year, month)) = infer_month(&uri_to_path(uri)) else {
+        return json!([]);
+    };
+    let mut file = MonthFile::parse(text);
+    if file.validate((year, month)).is_err() {
+        return json!([]);
+    }
+
+    file.format();
+
+    json!([{
+        "range": {
+            "start": { "line": 0, "character": 0 },
+            "end": { "line": text.lines().count() + 1, "character": 0 },
+        },
+        "newText": file.text(),
+    }])
+}
+
+fn position_of(position: &Value) -> (usize, usize) {
+    (
+        position["line"].as_u64().unwrap_or_default() as usize,
+        position["character"].as_u64().unwrap_or_default() as usize,
+    )
+}
@@ -0,0 +1,270 @@
+use anyhow::Context;
+use itertools::Itertools;
+use rust_decimal::Decimal;
+
+use std::{collections::BTreeMap, io::ErrorKind, path::Path};
+
+use crate::calculate;
+
+// a month file kept as plain lines so that it can be edited
+// in place without losing comments and formatting
+#[derive(Clone, Default)]
+pub struct MonthFile {
+    lines: Vec<String>,
+}
+
+struct Section {
+    day: u32,
+    // the line with the day itself
+    start: usize,
+    // the last line with expenses (or the day line if there are none yet)
+    last_data: usize,
+    // one past the last line of the section (comments included)
+    end: usize,
+}
+
+impl MonthFile {
+    // a missing file is treated as an empty one
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        match fs_err::read_to_string(path) {
+            Ok(text) => Ok(Self::parse(&text)),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err).context("failed to read month file"),
+        }
+    }
+
+    pub fn parse(text: &str) -> Self {
+        Self {
+            lines: text.lines().map(str::to_string).collect(),
+        }
+    }
+
+    pub fn text(&self) -> String {
+        let mut text = self.lines.join("\n");
+        text.push('\n');
+        text
+    }
+
+    // checks the file with the very same parser which is used for drawing
+    pub fn validate(&self, (year, month): (i32, u32)) -> anyhow::Result<()> {
+        calculate(&self.text(), (year, month)).map(|_| ())
+    }
+
+    // written to a temporary file first so that a failure never leaves a half written file
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        // unwrap: month files always have a file name
+        let name = path.file_name().unwrap().to_string_lossy();
+        let tmp = path.with_file_name(format!(".{name}.tmp"));
+
+        fs_err::write(&tmp, self.text()).context("failed to write month file")?;
+        fs_err::rename(&tmp, path).context("failed to replace month file")?;
+
+        Ok(())
+    }
+
+    // comment texts without the leading `#`
+    pub fn comments(&self) -> impl Iterator<Item = &str> {
+        self.lines
+            .iter()
+            .filter_map(|line| line.trim().strip_prefix('#'))
+            .map(str::trim)
+    }
+
+    // comments inside day sections (without the leading `#`) by day
+    pub fn day_comments(&self) -> BTreeMap<u32, Vec<&str>> {
+        let mut comments = BTreeMap::<u32, Vec<&str>>::new();
+        for section in self.sections() {
+            for line in &self.lines[section.start..section.end] {
+                if let Some(comment) = line.trim().strip_prefix('#') {
+                    comments
+                        .entry(section.day)
+                        .or_default()
+                        .push(comment.trim());
+                }
+            }
+        }
+
+        comments
+    }
+
+    // merges the amounts into an existing line of the category or adds a new one.
+    // the day section is created (in order) if it does not exist yet
+    pub fn add(&mut self, day: u32, category: &str, amounts: &[Decimal]) {
+        let values = amounts.iter().map(|amount| amount.to_string()).join(" ");
+        let sections = self.sections();
+
+        if let Some(section) = sections.iter().find(|section| section.day == day) {
+            for i in (section.start + 1)..section.end {
+                if self.lines[i].split_whitespace().next() == Some(category) {
+                    let line = self.lines[i].trim_end();
+                    self.lines[i] = data_line(line, &values);
+                    return;
+                }
+            }
+
+            self.lines
+                .insert(section.last_data + 1, data_line(category, &values));
+            return;
+        }
+
+        let lines = [day.to_string(), data_line(category, &values)];
+        match sections.iter().find(|section| section.day > day) {
+            Some(next) => {
+                // comments right above the next day belong to it
+                let mut at = next.start;
+                while at > 0 && self.lines[at - 1].trim().starts_with('#') {
+                    at -= 1;
+                }
+
+                let lines = lines.into_iter().chain([String::new()]);
+                self.lines.splice(at..at, lines);
+            }
+            None => {
+                if let Some(last) = self.lines.last() {
+                    if !last.trim().is_empty() {
+                        self.lines.push(String::new());
+                    }
+                }
+                self.lines.extend(lines);
+            }
+        }
+    }
+
+    // drops the line of the category. the day itself stays even if it becomes empty
+    pub fn remove(&mut self, day: u32, category: &str) -> anyhow::Result<()> {
+        let section = self
+            .sections()
+            .into_iter()
+            .find(|section| section.day == day)
+            .context(format!("no such day: {day}"))?;
+
+        let i = ((section.start + 1)..section.end)
+            .find(|i| self.lines[*i].split_whitespace().next() == Some(category))
+            .context(format!("no such category (day: {day}): {category}"))?;
+        self.lines.remove(i);
+
+        Ok(())
+    }
+
+    // rewrites the file in the canonical layout: days in order separated by a single
+    // blank line, single spaces between tokens. comments stay with the day they are
+    // in (the ones between days go with the following day). meant for valid files only
+    pub fn format(&mut self) {
+        let mut header = vec![];
+        let mut sections = BTreeMap::<u32, Vec<String>>::new();
+        let mut pending = vec![];
+        let mut current = None::<u32>;
+
+        for line in &self.lines {
+            let line = line.split_whitespace().join(" ");
+
+            if line.is_empty() {
+                current = None;
+                continue;
+            }
+
+            if let Some(comment) = line.strip_prefix('#') {
+                let comment = format!("# {}", comment.trim());
+                match current {
+                    Some(day) => sections.entry(day).or_default().push(comment),
+                    None => pending.push(comment),
+                }
+                continue;
+            }
+
+            match current {
+                Some(day) => sections.entry(day).or_default().push(line),
+                None => match line.parse::<u32>() {
+                    Ok(day) => {
+                        let section = sections.entry(day).or_default();
+                        section.append(&mut pending);
+                        section.push(day.to_string());
+                        current = Some(day);
+                    }
+                    // not a valid file, keep whatever this is at the top
+                    Err(_) => header.push(line),
+                },
+            }
+        }
+
+        let mut blocks = vec![];
+        if !header.is_empty() {
+            blocks.push(header);
+        }
+        blocks.extend(sections.into_values());
+        if !pending.is_empty() {
+            blocks.push(pending);
+        }
+
+        self.lines.clear();
+        for block in blocks {
+            if !self.lines.is_empty() {
+                self.lines.push(String::new());
+            }
+            self.lines.extend(block);
+        }
+    }
+
+    // appends a comment to the end of an existing day section
+    pub fn add_comment(&mut self, day: u32, comment: &str) -> anyhow::Result<()> {
+        let section = self
+            .sections()
+            .into_iter()
+            .find(|section| section.day == day)
+            .context(format!("no such day: {day}"))?;
+
+        self.lines.insert(section.end, format!("# {comment}"));
+
+        Ok(())
+    }
+
+    fn sections(&self) -> Vec<Section> {
+        let mut sections = vec![];
+        let mut current = None::<Section>;
+
+        for (i, line) in self.lines.iter().enumerate() {
+            let line = line.trim();
+
+            if line.is_empty() {
+                sections.extend(current.take());
+                continue;
+            }
+
+            if line.starts_with('#') {
+                if let Some(section) = current.as_mut() {
+                    section.end = i + 1;
+                }
+                continue;
+            }
+
+            match current.as_mut() {
+                Some(section) => {
+                    section.last_data = i;
+                    section.end = i + 1;
+                }
+                None => {
+                    // garbage is reported by `validate` so it is fine to skip it here
+                    if let Ok(day) = line.parse::<u32>() {
+                        current = Some(Section {
+                            day,
+                            start: i,
+                            last_data: i,
+                            end: i + 1,
+                        });
+                    }
+                }
+            }
+        }
+
+        sections.extend(current);
+        sections
+    }
+}
+
+fn data_line(prefix: &str, values: &str) -> String {
+    if values.is_empty() {
+        prefix.to_string()
+    } else {
+        format!("{prefix} {values}")
+    }
+}
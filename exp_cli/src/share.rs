@@ -0,0 +1,236 @@
+use anyhow::ensure;
+use plotters::{
+    prelude::*,
+    style::text_anchor::{HPos, Pos, VPos},
+};
+
+use std::path::Path;
+
+use crate::{chart, draw_filter_note, totals, Stats};
+
+struct Share {
+    category: String,
+    value: f32,
+    color: RGBColor,
+}
+
+fn shares(stats: &Stats, ordered_categories: Vec<String>) -> anyhow::Result<(Vec<Share>, f32)> {
+    let mut totals = totals(stats);
+    let total = totals.values().sum::<f32>();
+
+    ensure!(total > 0.0, "there are no expenses to draw");
+
+    let shares = chart::colored(&ordered_categories)
+        .into_iter()
+        .filter_map(|(category, color)| {
+            let value = totals.remove(&category)?;
+            (value > 0.0).then_some(Share {
+                category,
+                value,
+                color,
+            })
+        })
+        .collect::<Vec<Share>>();
+
+    Ok((shares, total))
+}
+
+pub fn draw_pie(
+    stats: &Stats,
+    ordered_categories: Vec<String>,
+    donut: bool,
+    excluded: &[String],
+    output: impl AsRef<Path>,
+) -> anyhow::Result<()> {
+    let (shares, total) = shares(stats, ordered_categories)?;
+
+    let canvas = BitMapBackend::new(&output, (640, 480)).into_drawing_area();
+    canvas.fill(&WHITE)?;
+
+    let canvas = canvas.margin(10, 10, 10, 10);
+    draw_filter_note(&canvas, excluded)?;
+
+    let canvas = canvas.titled("share", ("sans-serif", 40))?;
+
+    let (width, height) = canvas.dim_in_pixel();
+    let (x, y) = canvas.get_base_pixel();
+
+    // the pie is positioned in absolute (backend) coordinates
+    let center = (x + width as i32 / 2, y + height as i32 / 2);
+    let radius = width.min(height) as f64 * 0.35;
+
+    let sizes = shares
+        .iter()
+        .map(|share| share.value as f64)
+        .collect::<Vec<_>>();
+    let colors = shares.iter().map(|share| share.color).collect::<Vec<_>>();
+    let labels = shares
+        .iter()
+        .map(|share| {
+            let pct = share.value / total * 100.0;
+            format!("{} {pct:.1}%", share.category)
+        })
+        .collect::<Vec<_>>();
+
+    let mut pie = Pie::new(&center, &radius, &sizes, &colors, &labels);
+    pie.start_angle(-90.0);
+    pie.label_style(("sans-serif", 16).into_font().color(&BLACK));
+    if donut {
+        pie.donut_hole(radius * 0.5);
+    }
+
+    canvas.draw(&pie)?;
+
+    if donut {
+        canvas.draw(&Text::new(
+            format!("{total:.0}"),
+            (width as i32 / 2, height as i32 / 2),
+            ("sans-serif", 30)
+                .into_font()
+                .color(&BLACK)
+                .pos(Pos::new(HPos::Center, VPos::Center)),
+        ))?;
+    }
+
+    canvas.present()?;
+
+    Ok(())
+}
+
+pub fn draw_treemap(
+    stats: &Stats,
+    ordered_categories: Vec<String>,
+    excluded: &[String],
+    output: impl AsRef<Path>,
+) -> anyhow::Result<()> {
+    let (mut shares, total) = shares(stats, ordered_categories)?;
+    // the layout works best when the biggest tiles go first
+    shares.sort_by(|a, b| {
+        b.value
+            .partial_cmp(&a.value)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    let canvas = BitMapBackend::new(&output, (640, 480)).into_drawing_area();
+    canvas.fill(&WHITE)?;
+
+    let canvas = canvas.margin(10, 10, 10, 10);
+    draw_filter_note(&canvas, excluded)?;
+
+    let canvas = canvas.titled("share", ("sans-serif", 40))?;
+
+    let (width, height) = canvas.dim_in_pixel();
+    let (width, height) = (width as f64, height as f64);
+
+    let scale = width * height / total as f64;
+    let areas = shares
+        .iter()
+        .map(|share| share.value as f64 * scale)
+        .collect::<Vec<_>>();
+
+    for (share, (x, y, w, h)) in shares
+        .iter()
+        .zip(squarify(&areas, (0.0, 0.0, width, height)))
+    {
+        let (x0, y0) = (x.round() as i32, y.round() as i32);
+        let (x1, y1) = ((x + w).round() as i32, (y + h).round() as i32);
+
+        canvas.draw(&Rectangle::new([(x0, y0), (x1, y1)], share.color.filled()))?;
+        canvas.draw(&Rectangle::new([(x0, y0), (x1, y1)], WHITE.stroke_width(2)))?;
+
+        // do not even try to fit labels into tiny tiles
+        if w < 50.0 || h < 40.0 {
+            continue;
+        }
+
+        let font = ("sans-serif", 16)
+            .into_font()
+            .color(&text_color(share.color));
+        let center = ((x + w / 2.0) as i32, (y + h / 2.0) as i32);
+        canvas.draw(&Text::new(
+            share.category.clone(),
+            center,
+            font.pos(Pos::new(HPos::Center, VPos::Bottom)),
+        ))?;
+        canvas.draw(&Text::new(
+            format!("{:.1}%", share.value / total * 100.0),
+            center,
+            font.pos(Pos::new(HPos::Center, VPos::Top)),
+        ))?;
+    }
+
+    canvas.present()?;
+
+    Ok(())
+}
+
+// dark text on light tiles and vice versa
+fn text_color(RGBColor(r, g, b): RGBColor) -> RGBColor {
+    let luminance = 0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32;
+
+    if luminance > 150.0 {
+        BLACK
+    } else {
+        WHITE
+    }
+}
+
+type Tile = (f64, f64, f64, f64);
+
+// squarified treemap layout (Bruls, Huizing, van Wijk). `areas` must be sorted
+// in descending order and add up to the area of `rect`
+fn squarify(areas: &[f64], rect: Tile) -> Vec<Tile> {
+    // the worst aspect ratio among the tiles of a row laid along `side`
+    let worst = |row: &[f64], side: f64| {
+        let sum = row.iter().sum::<f64>();
+        let max = row.iter().copied().fold(f64::MIN, f64::max);
+        let min = row.iter().copied().fold(f64::MAX, f64::min);
+
+        f64::max(
+            side * side * max / (sum * sum),
+            sum * sum / (side * side * min),
+        )
+    };
+
+    let (mut x, mut y, mut w, mut h) = rect;
+    let mut tiles = vec![];
+
+    let mut rest = areas;
+    while !rest.is_empty() {
+        let side = w.min(h);
+
+        let mut n = 1;
+        while n < rest.len() && worst(&rest[..n + 1], side) <= worst(&rest[..n], side) {
+            n += 1;
+        }
+
+        let (row, tail) = rest.split_at(n);
+        let sum = row.iter().sum::<f64>();
+
+        if w >= h {
+            // lay the row out as a column on the left
+            let thickness = sum / h;
+            let mut offset = y;
+            for area in row {
+                tiles.push((x, offset, thickness, area / thickness));
+                offset += area / thickness;
+            }
+            x += thickness;
+            w -= thickness;
+        } else {
+            // lay the row out as a line on the top
+            let thickness = sum / w;
+            let mut offset = x;
+            for area in row {
+                tiles.push((offset, y, area / thickness, thickness));
+                offset += area / thickness;
+            }
+            y += thickness;
+            h -= thickness;
+        }
+
+        rest = tail;
+    }
+
+    tiles
+}
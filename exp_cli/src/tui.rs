@@ -0,0 +1,462 @@
+use anyhow::Context;
+use chrono::{Datelike, Month, NaiveDate, Utc};
+use crossterm::{
+    cursor::{Hide, MoveTo, Show},
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    execute, queue,
+    style::Stylize,
+    terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use exp_core::parse;
+use itertools::Itertools;
+
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use crate::{calculate, infer_month, month_file::MonthFile, totals, Stats};
+
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    /// month file named like `jul-2023`
+    data_file: PathBuf,
+}
+
+const HELP: &str = "arrows/hjkl move  tab entries  a add  e edit  d delete  q quit";
+// how long to wait for a key before looking for external changes
+const KEY_TIMEOUT: Duration = Duration::from_millis(500);
+// the width of the entries column
+const LEFT_WIDTH: usize = 40;
+
+#[derive(PartialEq)]
+enum Focus {
+    Calendar,
+    Entries,
+}
+
+enum Prompt {
+    Add,
+    // the category being edited
+    Edit(String),
+}
+
+enum Key {
+    Char(char),
+    Up,
+    Down,
+    Left,
+    Right,
+    Enter,
+    Backspace,
+    Tab,
+    Esc,
+}
+
+struct App {
+    path: PathBuf,
+    month: (i32, u32),
+    file: MonthFile,
+    stats: Stats,
+    modified: Option<SystemTime>,
+    day: u32,
+    focus: Focus,
+    entry: usize,
+    prompt: Option<(Prompt, String)>,
+    status: String,
+}
+
+pub fn run(args: Args) -> anyhow::Result<()> {
+    let (year, month) = infer_month(&args.data_file)?;
+
+    let mut app = App {
+        path: args.data_file,
+        month: (year, month),
+        file: MonthFile::default(),
+        stats: Stats::new(),
+        modified: None,
+        day: 1,
+        focus: Focus::Calendar,
+        entry: 0,
+        prompt: None,
+        status: String::new(),
+    };
+    app.reload().context("failed to load data file")?;
+
+    let today = Utc::now().date_naive();
+    if (today.year(), today.month()) == (year, month) {
+        app.day = today.day();
+    }
+
+    let _terminal = Terminal::enter()?;
+
+    app.draw()?;
+    loop {
+        let keys = read_keys()?;
+
+        // nothing has been pressed for a while, a good time to look for external changes
+        if keys.is_empty() && !app.reload_if_changed() {
+            continue;
+        }
+
+        for key in keys {
+            if !app.handle(key) {
+                return Ok(());
+            }
+        }
+
+        app.draw()?;
+    }
+}
+
+impl App {
+    fn reload(&mut self) -> anyhow::Result<()> {
+        let modified = modified(&self.path);
+        let file = MonthFile::load(&self.path)?;
+        let (stats, _ordered_categories) = calculate(&file.text(), self.month)?;
+
+        self.file = file;
+        self.stats = stats;
+        self.modified = modified;
+        self.entry = self.entry.min(self.entries().len().saturating_sub(1));
+
+        Ok(())
+    }
+
+    fn reload_if_changed(&mut self) -> bool {
+        let modified = modified(&self.path);
+        if modified == self.modified {
+            return false;
+        }
+
+        self.status = match self.reload() {
+            Ok(()) => "reloaded after an external change".to_string(),
+            Err(err) => {
+                // do not try again until the file changes once more
+                self.modified = modified;
+                format!("not reloaded: {err:#}")
+            }
+        };
+
+        true
+    }
+
+    fn days(&self) -> u32 {
+        self.stats.len() as u32
+    }
+
+    fn entries(&self) -> Vec<(&String, &Vec<f32>)> {
+        self.stats
+            .get(&self.day)
+            .map(|day_stats| {
+                day_stats
+                    .iter()
+                    .sorted_by(|(a, _), (b, _)| a.cmp(b))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    // returns false when it is time to quit
+    fn handle(&mut self, key: Key) -> bool {
+        if let Some((prompt, mut input)) = self.prompt.take() {
+            match key {
+                Key::Char(c) => input.push(c),
+                Key::Backspace => {
+                    input.pop();
+                }
+                Key::Esc => return true,
+                Key::Enter => {
+                    self.submit(prompt, &input);
+                    return true;
+                }
+                _ => {}
+            }
+            self.prompt = Some((prompt, input));
+            return true;
+        }
+
+        self.status.clear();
+
+        match (key, &self.focus) {
+            (Key::Char('q'), _) => return false,
+            (Key::Tab, Focus::Calendar) => self.focus = Focus::Entries,
+            (Key::Tab | Key::Esc, Focus::Entries) => self.focus = Focus::Calendar,
+            (Key::Char('a'), _) => self.prompt = Some((Prompt::Add, String::new())),
+            (Key::Left | Key::Char('h'), Focus::Calendar) => self.move_day(-1),
+            (Key::Right | Key::Char('l'), Focus::Calendar) => self.move_day(1),
+            (Key::Up | Key::Char('k'), Focus::Calendar) => self.move_day(-7),
+            (Key::Down | Key::Char('j'), Focus::Calendar) => self.move_day(7),
+            (Key::Up | Key::Char('k'), Focus::Entries) => {
+                self.entry = self.entry.saturating_sub(1);
+            }
+            (Key::Down | Key::Char('j'), Focus::Entries) => {
+                self.entry = (self.entry + 1).min(self.entries().len().saturating_sub(1));
+            }
+            (Key::Char('e'), Focus::Entries) => {
+                if let Some((category, values)) = self.entries().get(self.entry) {
+                    let input = format!("{category} {}", values.iter().join(" "));
+                    self.prompt = Some((Prompt::Edit(category.to_string()), input));
+                }
+            }
+            (Key::Char('d'), Focus::Entries) => {
+                if let Some((category, _values)) = self.entries().get(self.entry) {
+                    let category = category.to_string();
+                    let day = self.day;
+                    self.edit(|file| file.remove(day, &category));
+                }
+            }
+            _ => {}
+        }
+
+        true
+    }
+
+    fn move_day(&mut self, delta: i32) {
+        let day = self.day as i32 + delta;
+        if (1..=self.days() as i32).contains(&day) {
+            self.day = day as u32;
+            self.entry = 0;
+        }
+    }
+
+    fn submit(&mut self, prompt: Prompt, input: &str) {
+        if input.trim().is_empty() {
+            return;
+        }
+        // the same grammar as the data lines of the file
+        let (category, amounts) = match parse::data_line(&input.split_whitespace().join(" "), false)
+        {
+            Ok(entry) => entry,
+            Err(err) => {
+                self.status = format!("failed to parse entry: {err:#}");
+                return;
+            }
+        };
+        let day = self.day;
+        self.edit(|file| {
+            if let Prompt::Edit(old) = &prompt {
+                file.remove(day, old)?;
+            }
+            file.add(day, &category, &amounts);
+            Ok(())
+        });
+    }
+
+    // every change goes through the formatter and is validated before it is written
+    fn edit(&mut self, change: impl FnOnce(&mut MonthFile) -> anyhow::Result<()>) {
+        // changes made elsewhere since the last look at the file would be overwritten
+        match MonthFile::load(&self.path) {
+            Ok(on_disk) if on_disk.text() == self.file.text() => {}
+            Ok(_) => {
+                self.status = match self.reload() {
+                    Ok(()) => "not saved: the file was changed elsewhere, reloaded".to_string(),
+                    Err(err) => format!("not saved: the file was changed elsewhere: {err:#}"),
+                };
+                return;
+            }
+            Err(err) => {
+                self.status = format!("not saved: {err:#}");
+                return;
+            }
+        }
+
+        let mut file = self.file.clone();
+
+        let result = change(&mut file).and_then(|()| {
+            file.format();
+            let (stats, _ordered_categories) = calculate(&file.text(), self.month)?;
+            file.save(&self.path)?;
+            Ok(stats)
+        });
+
+        match result {
+            Ok(stats) => {
+                self.file = file;
+                self.stats = stats;
+                self.modified = modified(&self.path);
+                self.entry = self.entry.min(self.entries().len().saturating_sub(1));
+                self.status = "saved".to_string();
+            }
+            Err(err) => self.status = format!("not saved: {err:#}"),
+        }
+    }
+
+    fn draw(&self) -> anyhow::Result<()> {
+        let (year, month) = self.month;
+        let day_totals = self
+            .stats
+            .iter()
+            .map(|(day, day_stats)| (*day, day_stats.values().flatten().sum::<f32>()))
+            .collect::<Vec<_>>();
+        let total = day_totals.iter().map(|(_day, total)| total).sum::<f32>();
+
+        let mut lines = vec![];
+
+        // unwrap: `infer_month` only returns valid months
+        let name = Month::try_from(month as u8).unwrap().name();
+        lines.push(format!("{name} {year}  total: {total:.2}"));
+        lines.push(String::new());
+
+        // the calendar
+        lines.push(
+            ["Mo", "Tu", "We", "Th", "Fr", "Sa", "Su"]
+                .iter()
+                .map(|name| format!("{name:<11}"))
+                .join(""),
+        );
+        // unwrap: the first day exists in every month
+        let offset = NaiveDate::from_ymd_opt(year, month, 1)
+            .unwrap()
+            .weekday()
+            .num_days_from_monday() as usize;
+        let cells = std::iter::repeat_n(None, offset)
+            .chain(day_totals.iter().map(Some))
+            .collect::<Vec<_>>();
+        for week in cells.chunks(7) {
+            let line = week
+                .iter()
+                .map(|cell| match cell {
+                    None => " ".repeat(11),
+                    Some((day, total)) => {
+                        let total = if *total > 0.0 {
+                            format!("{total:.0}")
+                        } else {
+                            String::new()
+                        };
+                        let cell = format!("{day:>2} {total:>7}");
+                        if *day == self.day {
+                            format!("{} ", cell.reverse())
+                        } else {
+                            format!("{cell} ")
+                        }
+                    }
+                })
+                .join("");
+            lines.push(line);
+        }
+        lines.push(String::new());
+
+        // entries of the selected day on the left, month totals on the right
+        // padded before highlighting as escape codes take no room on the screen
+        let mut left = vec![format!("{:<LEFT_WIDTH$}", format!("day {}", self.day))];
+        for (i, (category, values)) in self.entries().into_iter().enumerate() {
+            let sum = if values.is_empty() {
+                String::new()
+            } else {
+                format!("{:.2}", values.iter().sum::<f32>())
+            };
+            let entry = format!(
+                "{:<LEFT_WIDTH$}",
+                format!("{category:<14} {:<16} {sum:>7}", values.iter().join(" "))
+            );
+            if self.focus == Focus::Entries && i == self.entry {
+                left.push(entry.reverse().to_string());
+            } else {
+                left.push(entry);
+            }
+        }
+
+        let mut right = vec!["month".to_string()];
+        for (category, value) in
+            totals(&self.stats)
+                .into_iter()
+                .sorted_by(|(a, a_value), (b, b_value)| {
+                    b_value
+                        .partial_cmp(a_value)
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                        .then(a.cmp(b))
+                })
+        {
+            let share = if total > 0.0 {
+                value / total * 100.0
+            } else {
+                0.0
+            };
+            right.push(format!("{category:<14} {value:>9.2} {share:>5.1}%"));
+        }
+
+        let (empty_left, empty_right) = (" ".repeat(LEFT_WIDTH), String::new());
+        for row in left.iter().zip_longest(right.iter()) {
+            let (left, right) = row.or(&empty_left, &empty_right);
+            lines.push(format!("{left}    {right}"));
+        }
+        lines.push(String::new());
+
+        match &self.prompt {
+            Some((Prompt::Add, input)) => lines.push(format!("add: {input}_")),
+            Some((Prompt::Edit(_), input)) => lines.push(format!("edit: {input}_")),
+            None => lines.push(HELP.to_string()),
+        }
+        lines.push(self.status.clone());
+
+        // the terminal is in raw mode so every line needs an explicit carriage return
+        let mut stdout = std::io::stdout().lock();
+        queue!(stdout, MoveTo(0, 0), Clear(ClearType::All))?;
+        write!(stdout, "{}", lines.join("\r\n"))?;
+        stdout.flush()?;
+
+        Ok(())
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs_err::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+// empty when nothing has been pressed before the wait timed out. all the keys
+// which are already there are returned at once (pasted text, fast typing)
+fn read_keys() -> anyhow::Result<Vec<Key>> {
+    let mut keys = vec![];
+    let mut timeout = KEY_TIMEOUT;
+    while event::poll(timeout).context("failed to read from terminal")? {
+        timeout = Duration::ZERO;
+
+        let Event::Key(key) = event::read().context("failed to read from terminal")? else {
+            continue;
+        };
+        if key.kind == KeyEventKind::Release {
+            continue;
+        }
+        let key = match key.code {
+            KeyCode::Up => Key::Up,
+            KeyCode::Down => Key::Down,
+            KeyCode::Left => Key::Left,
+            KeyCode::Right => Key::Right,
+            KeyCode::Enter => Key::Enter,
+            KeyCode::Backspace => Key::Backspace,
+            KeyCode::Tab => Key::Tab,
+            KeyCode::Esc => Key::Esc,
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => Key::Char(c),
+            _ => continue,
+        };
+        keys.push(key);
+    }
+
+    Ok(keys)
+}
+
+// raw mode on the alternate screen for as long as it lives
+struct Terminal;
+
+impl Terminal {
+    fn enter() -> anyhow::Result<Self> {
+        terminal::enable_raw_mode().context("failed to enter raw mode")?;
+        // from here on the terminal is restored on drop, even if the rest fails
+        let terminal = Self;
+        execute!(std::io::stdout(), EnterAlternateScreen, Hide)?;
+
+        Ok(terminal)
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        // nothing sensible can be done about errors while restoring the terminal
+        let _ = execute!(std::io::stdout(), Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
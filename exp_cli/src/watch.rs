@@ -0,0 +1,144 @@
+use anyhow::Context;
+use notify::{EventKind, RecursiveMode, Watcher};
+
+use std::{
+    collections::BTreeSet,
+    ffi::OsStr,
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::Duration,
+};
+
+use crate::{infer_month, render, DrawArgs};
+
+// editors usually produce a burst of events on every save
+const DEBOUNCE: Duration = Duration::from_millis(100);
+
+pub fn run(args: &DrawArgs) -> anyhow::Result<()> {
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx).context("failed to create file watcher")?;
+
+    // the parent directory is watched rather than the file itself because
+    // a lot of editors save by writing a new file and renaming it over the old one
+    let dir = if args.data_file.is_dir() {
+        fs_err::create_dir_all(&args.output).context("failed to create output directory")?;
+        args.data_file.clone()
+    } else {
+        match args.data_file.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        }
+    };
+
+    watcher
+        .watch(&dir, RecursiveMode::NonRecursive)
+        .context(format!("failed to watch {}", dir.display()))?;
+
+    for data_file in initial(args, &dir)? {
+        rerender(args, &data_file);
+    }
+
+    eprintln!("watching {} (ctrl-c to stop)", args.data_file.display());
+
+    loop {
+        let mut changed = BTreeSet::new();
+
+        let event = rx.recv().context("file watcher stopped")?;
+        collect(event, &mut changed);
+        while let Ok(event) = rx.recv_timeout(DEBOUNCE) {
+            collect(event, &mut changed);
+        }
+
+        for path in changed {
+            if !is_watched(args, &path) {
+                continue;
+            }
+
+            if args.data_file.is_dir() {
+                rerender(args, &path);
+            } else {
+                rerender(args, &args.data_file);
+            }
+        }
+    }
+}
+
+fn collect(event: notify::Result<notify::Event>, changed: &mut BTreeSet<PathBuf>) {
+    match event {
+        Ok(event) => {
+            if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                changed.extend(event.paths);
+            }
+        }
+        Err(err) => eprintln!("file watcher error: {err}"),
+    }
+}
+
+fn initial(args: &DrawArgs, dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    if !args.data_file.is_dir() {
+        return Ok(vec![args.data_file.clone()]);
+    }
+
+    let mut data_files = vec![];
+    for entry in fs_err::read_dir(dir).context("failed to read data directory")? {
+        let path = entry.context("failed to read data directory")?.path();
+        if is_watched(args, &path) {
+            data_files.push(path);
+        }
+    }
+    data_files.sort();
+
+    Ok(data_files)
+}
+
+fn is_watched(args: &DrawArgs, path: &Path) -> bool {
+    if !path.is_file() {
+        return false;
+    }
+
+    if !args.data_file.is_dir() {
+        return path.file_name() == args.data_file.file_name();
+    }
+
+    // skip hidden files (including our own temporary images), images
+    // and everything else which does not look like a month file
+    let hidden = path
+        .file_name()
+        .and_then(OsStr::to_str)
+        .map(|name| name.starts_with('.'))
+        .unwrap_or(true);
+    let image = path.extension() == Some(OsStr::new("png"));
+
+    !hidden && !image && infer_month(path).is_ok()
+}
+
+fn output(args: &DrawArgs, data_file: &Path) -> PathBuf {
+    if args.data_file.is_dir() {
+        // unwrap: only files get this far
+        let name = data_file.file_stem().unwrap();
+        args.output.join(name).with_extension("png")
+    } else {
+        args.output.clone()
+    }
+}
+
+// errors are reported without stopping the watch. the image is only
+// replaced after a successful render so the last good one is kept
+fn rerender(args: &DrawArgs, data_file: &Path) {
+    let output = output(args, data_file);
+
+    // unwrap: output paths always have a file name
+    let name = output.file_name().unwrap().to_string_lossy();
+    let tmp = output.with_file_name(format!(".{name}"));
+
+    let result = render(args, data_file, &tmp)
+        .and_then(|()| fs_err::rename(&tmp, &output).context("failed to replace output"));
+
+    match result {
+        Ok(()) => eprintln!("rendered {}", output.display()),
+        Err(err) => {
+            let _ = fs_err::remove_file(&tmp);
+            eprintln!("failed to render {}: {err:#}", data_file.display());
+        }
+    }
+}
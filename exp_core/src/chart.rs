@@ -0,0 +1,426 @@
+// the charts both exp_cli and exp_bot draw, on a canvas of the caller's
+// (a file for exp_cli, an in-memory png for exp_bot)
+
+use chrono::{Datelike, NaiveDate, Utc, Weekday};
+use plotters::{
+    backend::RGBPixel,
+    coord::{combinators::WithKeyPoints, types::RangedCoordu32, Shift},
+    prelude::*,
+    style::text_anchor::{HPos, Pos, VPos},
+};
+
+use std::collections::{BTreeMap, HashMap};
+
+// amounts by category for every day of the month
+pub type Stats = BTreeMap<u32, HashMap<String, Vec<f32>>>;
+
+pub type Canvas<'a> = DrawingArea<BitMapBackend<'a, RGBPixel>, Shift>;
+
+// the palette starts over when there are more categories than colors
+pub fn colored(ordered_categories: &[String]) -> Vec<(String, RGBColor)> {
+    ordered_categories
+        .iter()
+        .cloned()
+        .zip(colors::colors().into_iter().cycle())
+        .collect()
+}
+
+pub fn totals(stats: &Stats) -> HashMap<String, f32> {
+    let mut totals = HashMap::<String, f32>::new();
+    for day_stats in stats.values() {
+        for (category, values) in day_stats {
+            *totals.entry(category.clone()).or_default() += values.iter().sum::<f32>();
+        }
+    }
+
+    totals
+}
+
+// the month by day with the average day next to it
+pub fn draw_regular(
+    canvas: &Canvas,
+    (year, month): (i32, u32),
+    stats: Stats,
+    ordered_categories: &[String],
+    day_totals: bool,
+) -> anyhow::Result<()> {
+    let colored_ordered_categories = colored(ordered_categories);
+
+    draw_main_chart(
+        (year, month),
+        stats.clone(),
+        &colored_ordered_categories,
+        day_totals,
+        canvas,
+    )?;
+    draw_avg_chart((year, month), stats, &colored_ordered_categories, canvas)?;
+
+    Ok(())
+}
+
+fn draw_avg_chart(
+    (year, month): (i32, u32),
+    stats: Stats,
+    colored_ordered_categories: &[(String, RGBColor)],
+    canvas: &Canvas,
+) -> anyhow::Result<()> {
+    let x_range = 0u32..1u32;
+    let y_range = {
+        let max = stats
+            .values()
+            .map(|day_stats| day_stats.values().flatten().sum::<f32>())
+            .max_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+            .unwrap_or_default();
+
+        0f32..max
+    };
+
+    let mut chart = ChartBuilder::on(canvas)
+        .margin_right(520)
+        .caption("avg", ("sans-serif", 40).into_font())
+        .x_label_area_size(20)
+        .y_label_area_size(40)
+        .build_cartesian_2d(x_range, y_range)?;
+
+    chart
+        .configure_mesh()
+        // .disable_x_mesh()
+        .bold_line_style(WHITE.mix(0.3))
+        .disable_x_axis()
+        .set_tick_mark_size(LabelAreaPosition::Bottom, 0)
+        // .y_desc("Count")
+        // .x_desc("Bucket")
+        // .axis_desc_style(("sans-serif", 15))
+        .draw()?;
+
+    chart.draw_series([Rectangle::new([(0, 0.0), (1, 0.0)], BLACK)])?;
+
+    let days = days_so_far((year, month), &stats);
+
+    let mut totals = totals(&stats);
+
+    let total = totals.values().sum::<f32>();
+    let avg = total / days as f32;
+
+    let mut series = vec![];
+    let mut level = 0.0;
+    for (category, color) in colored_ordered_categories {
+        if let Some(ctotal) = totals.remove(category) {
+            let adjusted_total = avg * (ctotal / total);
+            series.push(Rectangle::new(
+                [(0, level), (1, level + adjusted_total)],
+                ShapeStyle {
+                    color: (*color).into(),
+                    filled: true,
+                    stroke_width: 0,
+                },
+            ));
+            level += adjusted_total;
+        }
+    }
+
+    chart.draw_series(series)?;
+
+    Ok(())
+}
+
+fn draw_main_chart(
+    (year, month): (i32, u32),
+    mut stats: Stats,
+    colored_ordered_categories: &[(String, RGBColor)],
+    day_totals: bool,
+    canvas: &Canvas,
+) -> anyhow::Result<()> {
+    let month_totals = totals(&stats);
+
+    let days = stats.len() as u32;
+    let x_range = day_range(days);
+    let y_range = {
+        let max = stats
+            .values()
+            .map(|day_stats| day_stats.values().flatten().sum::<f32>())
+            .max_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+            .unwrap_or_default();
+
+        y_range(max, day_totals)
+    };
+
+    let mut chart = ChartBuilder::on(canvas)
+        .caption("main", ("sans-serif", 40).into_font())
+        .margin_left(100)
+        .x_label_area_size(20)
+        .y_label_area_size(40)
+        .build_cartesian_2d(x_range, y_range)?;
+
+    chart
+        .configure_mesh()
+        .disable_x_mesh()
+        .bold_line_style(WHITE.mix(0.3))
+        .x_label_formatter(&day_label)
+        .x_label_style(("sans-serif", 10))
+        // .y_desc("Count")
+        // .x_desc("Bucket")
+        // .axis_desc_style(("sans-serif", 15))
+        .draw()?;
+
+    chart.draw_series(weekends((year, month), days, chart.y_range().end))?;
+    chart.draw_series([Rectangle::new([(0, 0.0), (days * 2, 0.0)], BLACK)])?;
+
+    let mut totals = HashMap::<u32, f32>::new();
+    for (category, color) in colored_ordered_categories {
+        let style = ShapeStyle {
+            color: (*color).into(),
+            filled: true,
+            stroke_width: 0,
+        };
+
+        let mut series = vec![];
+        for (day, day_stats) in stats.iter_mut() {
+            if let Some(values) = day_stats.remove(category) {
+                let value = values.into_iter().sum::<f32>();
+                let total = totals.get(day).copied().unwrap_or_default();
+
+                let (x0, x1) = day_span(*day);
+                series.push(Rectangle::new([(x0, total), (x1, total + value)], style));
+
+                totals
+                    .entry(*day)
+                    .and_modify(|t| {
+                        *t += value;
+                    })
+                    .or_insert(value);
+            }
+        }
+
+        if !series.is_empty() {
+            chart
+                .draw_series(series)?
+                .legend(move |(x, y)| Circle::new((x, y), 3, style))
+                .label(legend_label(category, &month_totals));
+        }
+    }
+
+    if day_totals {
+        chart.draw_series(day_total_labels(&totals))?;
+    }
+
+    chart
+        .configure_series_labels()
+        .position(SeriesLabelPosition::UpperRight)
+        .margin(20)
+        .legend_area_size(5)
+        .border_style(BLUE)
+        .background_style(BLUE.mix(0.1))
+        .label_font(("Calibri", 20))
+        .draw()
+        .unwrap();
+
+    Ok(())
+}
+
+// how the running daily average changed through the month
+pub fn draw_average_by_day(
+    canvas: &Canvas,
+    (year, month): (i32, u32),
+    stats: Stats,
+    ordered_categories: &[String],
+    day_totals: bool,
+) -> anyhow::Result<()> {
+    let colored_ordered_categories = colored(ordered_categories);
+
+    let days = stats.len() as u32;
+    let x_range = day_range(days);
+    let y_range = {
+        let max = stats
+            .values()
+            .map(|day_stats| day_stats.values().flatten().sum::<f32>())
+            .max_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+            .unwrap_or_default();
+
+        y_range(max, day_totals)
+    };
+
+    let mut chart = ChartBuilder::on(canvas)
+        .caption("avg by day", ("sans-serif", 40).into_font())
+        .x_label_area_size(20)
+        .y_label_area_size(40)
+        .build_cartesian_2d(x_range, y_range)?;
+
+    chart
+        .configure_mesh()
+        .disable_x_mesh()
+        .bold_line_style(WHITE.mix(0.3))
+        .x_label_formatter(&day_label)
+        .x_label_style(("sans-serif", 10))
+        .draw()?;
+
+    chart.draw_series(weekends((year, month), days, chart.y_range().end))?;
+    chart.draw_series([Rectangle::new([(0, 0.0), (days * 2, 0.0)], BLACK)])?;
+
+    let mut totals = HashMap::<String, f32>::new();
+    let mut avg_by_day = BTreeMap::<u32, HashMap<String, f32>>::new();
+    for (day, day_stats) in stats.iter() {
+        for (category, values) in day_stats {
+            *totals.entry(category.clone()).or_default() += values.iter().sum::<f32>();
+        }
+
+        let mut avg = HashMap::new();
+        for (category, total) in totals.iter() {
+            avg.insert(category.clone(), total / *day as f32);
+        }
+
+        avg_by_day.insert(*day, avg);
+    }
+
+    let today = {
+        let today = Utc::now();
+
+        if (year, month) == (today.year(), today.month()) {
+            Some(today.day())
+        } else {
+            None
+        }
+    };
+
+    let mut levels = HashMap::<u32, f32>::new();
+    for (category, color) in colored_ordered_categories {
+        let style = ShapeStyle {
+            color: color.into(),
+            filled: true,
+            stroke_width: 0,
+        };
+
+        let mut series = vec![];
+        for day in stats.keys() {
+            if let Some(today) = today.as_ref() {
+                if day > today {
+                    break;
+                }
+            }
+
+            if let Some(avg) = avg_by_day.get_mut(day) {
+                if let Some(value) = avg.remove(&category) {
+                    let level = levels.get(day).copied().unwrap_or_default();
+
+                    let (x0, x1) = day_span(*day);
+                    series.push(Rectangle::new([(x0, level), (x1, level + value)], style));
+
+                    *levels.entry(*day).or_default() += value;
+                }
+            }
+        }
+
+        if !series.is_empty() {
+            chart
+                .draw_series(series)?
+                .legend(move |(x, y)| Circle::new((x, y), 3, style))
+                .label(legend_label(&category, &totals));
+        }
+    }
+
+    if day_totals {
+        chart.draw_series(day_total_labels(&levels))?;
+    }
+
+    chart
+        .configure_series_labels()
+        .position(SeriesLabelPosition::UpperRight)
+        .margin(20)
+        .legend_area_size(5)
+        .border_style(BLUE)
+        .background_style(BLUE.mix(0.1))
+        .label_font(("Calibri", 20))
+        .draw()
+        .unwrap();
+
+    Ok(())
+}
+
+// the whole month unless it is still ongoing
+pub fn days_so_far((year, month): (i32, u32), stats: &Stats) -> u32 {
+    let today = Utc::now();
+
+    if (year, month) == (today.year(), today.month()) {
+        today.day()
+    } else {
+        stats.len() as u32
+    }
+}
+
+// every day takes two units of the x axis so that
+// its label can be put right in the middle of its bar
+pub fn day_range(days: u32) -> WithKeyPoints<RangedCoordu32> {
+    (0..days * 2).with_key_points((1..=days).map(|day| day * 2 - 1).collect())
+}
+
+pub fn day_span(day: u32) -> (u32, u32) {
+    ((day - 1) * 2, day * 2)
+}
+
+pub fn day_label(x: &u32) -> String {
+    (x / 2 + 1).to_string()
+}
+
+pub fn y_range(max: f32, day_totals: bool) -> std::ops::Range<f32> {
+    // leave some room for the day totals
+    if day_totals {
+        0f32..(max * 1.1)
+    } else {
+        0f32..max
+    }
+}
+
+pub fn weekends((year, month): (i32, u32), days: u32, height: f32) -> Vec<Rectangle<(u32, f32)>> {
+    (1..=days)
+        .filter(|day| {
+            NaiveDate::from_ymd_opt(year, month, *day)
+                .map(|date| matches!(date.weekday(), Weekday::Sat | Weekday::Sun))
+                .unwrap_or_default()
+        })
+        .map(|day| {
+            let (x0, x1) = day_span(day);
+            Rectangle::new([(x0, 0.0), (x1, height)], BLACK.mix(0.07).filled())
+        })
+        .collect()
+}
+
+pub fn day_total_labels(levels: &HashMap<u32, f32>) -> Vec<Text<'static, (u32, f32), String>> {
+    levels
+        .iter()
+        .filter(|(_day, level)| **level > 0.0)
+        .map(|(day, level)| {
+            let (x0, x1) = day_span(*day);
+            Text::new(
+                format!("{level:.0}"),
+                ((x0 + x1) / 2, *level),
+                ("sans-serif", 9)
+                    .into_font()
+                    .color(&BLACK)
+                    .pos(Pos::new(HPos::Center, VPos::Bottom)),
+            )
+        })
+        .collect()
+}
+
+pub fn legend_label(category: &str, totals: &HashMap<String, f32>) -> String {
+    let total = totals.values().sum::<f32>();
+    let ctotal = totals.get(category).copied().unwrap_or_default();
+
+    format!("{category} {ctotal:.0} ({:.0}%)", ctotal / total * 100.0)
+}
+
+pub mod colors {
+    use plotters::style::RGBColor;
+    pub fn colors() -> Vec<RGBColor> {
+        vec![
+            RGBColor(255, 0, 0),
+            RGBColor(0, 200, 0),
+            RGBColor(255, 255, 0),
+            RGBColor(0, 0, 200),
+            RGBColor(128, 128, 128),
+            RGBColor(255, 150, 150),
+            RGBColor(255, 165, 0),
+            RGBColor(0, 0, 0),
+        ]
+    }
+}
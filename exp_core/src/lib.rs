@@ -0,0 +1,3 @@
+// what exp_cli and exp_bot have to agree on
+pub mod chart;
+pub mod parse;
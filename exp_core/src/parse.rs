@@ -0,0 +1,160 @@
+// the grammar of month files. exp_cli draws them and exp_bot imports
+// them, so whatever is written there can be sent to the bot as is
+
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Context;
+use chrono::{Datelike, Days, Month, NaiveDate};
+use rust_decimal::Decimal;
+
+// `<day>` lines, each followed by the data lines of that day. sections are
+// separated by blank lines, comments (`#`) do not interrupt a section.
+// (date, category, amounts) for every data line. errors mention the line.
+// `strict` holds amounts to what the bot stores (see `bot_amount`)
+pub fn month(
+    text: &str,
+    (year, month): (i32, u32),
+    strict: bool,
+) -> anyhow::Result<Vec<(NaiveDate, String, Vec<Decimal>)>> {
+    let mut expenses = vec![];
+    let mut days = HashMap::<u32, HashSet<String>>::new();
+
+    let mut date = None;
+    for (n, line) in text.lines().enumerate() {
+        let n = n + 1;
+        let line = line.trim();
+
+        if line.is_empty() {
+            date = None;
+            continue;
+        }
+
+        if line.starts_with('#') {
+            continue;
+        }
+
+        let Some(date) = date else {
+            let day = line
+                .parse::<u32>()
+                .context(format!("failed to parse day (line: {n}): {line}"))?;
+            date = Some(
+                NaiveDate::from_ymd_opt(year, month, day)
+                    .context(format!("no such day in the month (line: {n}): {day}"))?,
+            );
+            if days
+                .get(&day)
+                .is_some_and(|categories| !categories.is_empty())
+            {
+                anyhow::bail!("duplicate entries (day: {day}, line: {n})");
+            }
+            days.entry(day).or_default();
+            continue;
+        };
+
+        let (category, amounts) =
+            data_line(line, strict).context(format!("failed to parse data line (line: {n})"))?;
+        // unwrap: the day has been inserted along with the date
+        if !days.get_mut(&date.day()).unwrap().insert(category.clone()) {
+            anyhow::bail!(
+                "duplicate category (day: {}, category: {category}, line: {n})",
+                date.day()
+            );
+        }
+
+        expenses.push((date, category, amounts));
+    }
+
+    Ok(expenses)
+}
+
+// month files are named like `jul-2023` or `2023-07` (the extension is ignored)
+pub fn file_month(file_name: &str) -> anyhow::Result<(i32, u32)> {
+    let stem = file_name
+        .rsplit_once('.')
+        .map_or(file_name, |(stem, _ext)| stem);
+    let (a, b) = stem
+        .split_once('-')
+        .context(format!("failed to infer month from file name: {stem}"))?;
+
+    let (month, year) = match a.parse::<u32>() {
+        Ok(_) => (b, a),
+        Err(_) => (a, b),
+    };
+
+    let year = year
+        .parse::<i32>()
+        .context(format!("failed to parse year: {year}"))?;
+    let month = match month.parse::<u32>() {
+        Ok(month) if (1..=12).contains(&month) => month,
+        Ok(_) => anyhow::bail!("failed to parse month: {month}"),
+        Err(_) => month
+            .parse::<Month>()
+            .map_err(|_| anyhow::anyhow!("failed to parse month: {month}"))?
+            .number_from_month(),
+    };
+
+    Ok((year, month))
+}
+
+// `<category> <amount>...`, tokens are separated by exactly one space
+pub fn data_line(line: &str, strict: bool) -> anyhow::Result<(String, Vec<Decimal>)> {
+    let mut tokens = line.split(' ');
+
+    let category = tokens
+        .next()
+        .filter(|category| !category.is_empty())
+        .context("failed to extract category")?
+        .to_string();
+    let amount = if strict { bot_amount } else { amount };
+    let amounts = tokens.map(amount).collect::<anyhow::Result<Vec<_>>>()?;
+
+    Ok((category, amounts))
+}
+
+// a data line with at least one amount (as the bot stores them) and an optional trailing
+// date (`today`, `yesterday` or `2023-07-14`), today by default
+pub fn quick_add(
+    line: &str,
+    today: NaiveDate,
+) -> anyhow::Result<(String, Vec<Decimal>, NaiveDate)> {
+    let (rest, date) = line
+        .rsplit_once(' ')
+        .and_then(|(rest, token)| Some((rest, date(token, today)?)))
+        .unwrap_or((line, today));
+
+    let (category, amounts) = data_line(rest, true)?;
+    if amounts.is_empty() {
+        anyhow::bail!("no amounts");
+    }
+
+    Ok((category, amounts, date))
+}
+
+// `today`, `yesterday` or `2023-07-14`
+pub fn date(token: &str, today: NaiveDate) -> Option<NaiveDate> {
+    match token {
+        "today" => Some(today),
+        "yesterday" => Some(today - Days::new(1)),
+        _ => NaiveDate::parse_from_str(token, "%Y-%m-%d").ok(),
+    }
+}
+
+// how a category is written in a month file (no whitespace)
+pub fn category_name(category: &str) -> String {
+    category.split_whitespace().collect::<Vec<_>>().join("-")
+}
+
+// any number, month files may well have refunds (negative amounts) in them
+pub fn amount(token: &str) -> anyhow::Result<Decimal> {
+    Decimal::from_str_exact(token).context(format!("failed to parse amount: {token}"))
+}
+
+// the bot stores amounts with two decimal places and they must be positive
+pub fn bot_amount(token: &str) -> anyhow::Result<Decimal> {
+    let amount = amount(token)?;
+    if amount <= Decimal::ZERO || amount.scale() > 2 {
+        anyhow::bail!("invalid amount: {token}");
+    }
+
+    Ok(amount)
+}